@@ -0,0 +1,631 @@
+use std::ops::Range;
+
+/// Sizes are in tokens, estimated from the word count since the embedder's
+/// tokenizer isn't exposed.
+#[derive(Debug, Clone, Copy)]
+pub struct ChunkerConfig {
+    pub chunk_size: usize,
+    pub chunk_overlap: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct Chunk {
+    /// Position of the chunk inside its document, starting at 0.
+    pub ordinal: usize,
+    pub text: String,
+    /// Closest Markdown heading above the chunk, if any.
+    pub heading: Option<String>,
+    /// Character offsets of the chunk in the original content.
+    pub start: usize,
+    pub end: usize,
+    pub location: ChunkLocation,
+}
+
+/// Where a chunk comes from in its document, when the format knows more than offsets.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ChunkLocation {
+    /// Named part of the document, like the chapter of a book or a spreadsheet's sheet.
+    pub section: Option<String>,
+    /// First and last spreadsheet rows, 1-based and inclusive.
+    pub rows: Option<(usize, usize)>,
+    /// First and last lines of source code, 1-based and inclusive.
+    pub lines: Option<(usize, usize)>,
+    /// Page of a PDF, starting at 1.
+    pub page: Option<usize>,
+}
+
+/// A byte range of the content that is chunked on its own, since everything in
+/// it shares the same location.
+#[derive(Debug, Clone)]
+pub struct Part {
+    pub range: Range<usize>,
+    pub location: ChunkLocation,
+}
+
+/// Splits documents into chunks that fit the embedder.
+///
+/// Boundaries are picked from the biggest structure that fits: Markdown headings
+/// first, then paragraphs, then sentences, and words as a last resort. Consecutive
+/// chunks of the same section share up to `chunk_overlap` tokens.
+pub struct Chunker {
+    config: ChunkerConfig,
+}
+
+// A piece of text that is never split further, as a byte range of the content.
+#[derive(Debug, Clone)]
+struct Unit {
+    range: Range<usize>,
+    tokens: usize,
+}
+
+struct Section {
+    heading: Option<String>,
+    range: Range<usize>,
+}
+
+impl Chunker {
+    pub fn new(config: ChunkerConfig) -> Self {
+        Self { config }
+    }
+
+    pub fn split(&self, content: &str) -> Vec<Chunk> {
+        self.split_parts(
+            content,
+            &[Part {
+                range: 0..content.len(),
+                location: ChunkLocation::default(),
+            }],
+        )
+    }
+
+    /// Splits each part separately, so no chunk spans two of them. Parts must be
+    /// sorted and must not overlap.
+    pub fn split_parts(&self, content: &str, parts: &[Part]) -> Vec<Chunk> {
+        let mut chunks = Vec::new();
+        let mut start_chars = CharOffsets::new(content);
+        let mut end_chars = CharOffsets::new(content);
+
+        for part in parts {
+            for section in Self::sections(content, part.range.clone()) {
+                let units = self.units(content, section.range);
+
+                for range in self.pack(units) {
+                    chunks.push(Chunk {
+                        ordinal: chunks.len(),
+                        text: content[range.clone()].to_string(),
+                        heading: section.heading.clone(),
+                        start: start_chars.char_offset(range.start),
+                        end: end_chars.char_offset(range.end),
+                        location: part.location.clone(),
+                    });
+                }
+            }
+        }
+
+        chunks
+    }
+
+    /// Packs consecutive parts into chunks of at most `chunk_size` tokens, for documents
+    /// made of small records like table rows. Parts are only grouped with neighbours
+    /// of the same section, and their row ranges are merged. Records are complete on
+    /// their own, so there is no overlap.
+    pub fn group_parts(&self, content: &str, parts: &[Part]) -> Vec<Chunk> {
+        let mut chunks = Vec::new();
+        let mut start_chars = CharOffsets::new(content);
+        let mut end_chars = CharOffsets::new(content);
+
+        let mut group: Vec<&Part> = Vec::new();
+        let mut tokens = 0;
+
+        for part in parts {
+            let part_tokens = Self::estimate_tokens(&content[part.range.clone()]);
+            let new_section = group
+                .first()
+                .is_some_and(|p| p.location.section != part.location.section);
+
+            if !group.is_empty() && (new_section || tokens + part_tokens > self.config.chunk_size) {
+                chunks.push(Self::merge_group(
+                    content,
+                    &group,
+                    &mut start_chars,
+                    &mut end_chars,
+                ));
+                group.clear();
+                tokens = 0;
+            }
+
+            // A record too big for a chunk is split like plain text.
+            if part_tokens > self.config.chunk_size {
+                chunks.extend(self.split_record(content, part, &mut start_chars, &mut end_chars));
+                continue;
+            }
+
+            group.push(part);
+            tokens += part_tokens;
+        }
+
+        if !group.is_empty() {
+            chunks.push(Self::merge_group(
+                content,
+                &group,
+                &mut start_chars,
+                &mut end_chars,
+            ));
+        }
+
+        for (ordinal, chunk) in chunks.iter_mut().enumerate() {
+            chunk.ordinal = ordinal;
+        }
+
+        chunks
+    }
+
+    fn merge_group(
+        content: &str,
+        group: &[&Part],
+        start_chars: &mut CharOffsets,
+        end_chars: &mut CharOffsets,
+    ) -> Chunk {
+        let first = group[0];
+        let last = group[group.len() - 1];
+
+        let span = |first: Option<(usize, usize)>, last: Option<(usize, usize)>| match (first, last)
+        {
+            (Some((start, _)), Some((_, end))) => Some((start, end)),
+            _ => None,
+        };
+
+        Chunk {
+            ordinal: 0,
+            text: content[first.range.start..last.range.end].to_string(),
+            heading: None,
+            start: start_chars.char_offset(first.range.start),
+            end: end_chars.char_offset(last.range.end),
+            location: ChunkLocation {
+                section: first.location.section.clone(),
+                rows: span(first.location.rows, last.location.rows),
+                lines: span(first.location.lines, last.location.lines),
+                page: first.location.page,
+            },
+        }
+    }
+
+    /// Splits a single record into paragraphs, sentences or words. Headings aren't
+    /// looked for, a `#` line is more likely a comment than a title here.
+    fn split_record(
+        &self,
+        content: &str,
+        part: &Part,
+        start_chars: &mut CharOffsets,
+        end_chars: &mut CharOffsets,
+    ) -> Vec<Chunk> {
+        let units = self.units(content, part.range.clone());
+
+        self.pack(units)
+            .into_iter()
+            .map(|range| {
+                let mut location = part.location.clone();
+
+                // Each piece of a long definition gets its own line range.
+                if let Some((first_line, _)) = part.location.lines {
+                    let line_of = |offset: usize| {
+                        first_line + content[part.range.start..offset].matches('\n').count()
+                    };
+                    location.lines = Some((line_of(range.start), line_of(range.end)));
+                }
+
+                Chunk {
+                    ordinal: 0,
+                    text: content[range.clone()].to_string(),
+                    heading: None,
+                    start: start_chars.char_offset(range.start),
+                    end: end_chars.char_offset(range.end),
+                    location,
+                }
+            })
+            .collect()
+    }
+
+    /// Rough token count, most tokenizers produce a bit more tokens than words.
+    pub fn estimate_tokens(text: &str) -> usize {
+        (text.split_whitespace().count() * 4).div_ceil(3)
+    }
+
+    fn sections(content: &str, part: Range<usize>) -> Vec<Section> {
+        let mut sections = Vec::new();
+        let mut current = Section {
+            heading: None,
+            range: part.start..part.start,
+        };
+
+        let mut offset = part.start;
+        for line in content[part.clone()].split_inclusive('\n') {
+            if let Some(heading) = Self::parse_heading(line) {
+                current.range.end = offset;
+                sections.push(current);
+
+                current = Section {
+                    heading: Some(heading),
+                    range: offset..offset,
+                };
+            }
+
+            offset += line.len();
+        }
+
+        current.range.end = part.end;
+        sections.push(current);
+
+        sections
+            .into_iter()
+            .filter(|s| !content[s.range.clone()].trim().is_empty())
+            .collect()
+    }
+
+    fn parse_heading(line: &str) -> Option<String> {
+        let line = line.trim();
+        let level = line.chars().take_while(|c| *c == '#').count();
+
+        if !(1..=6).contains(&level) {
+            return None;
+        }
+
+        let title = line[level..].strip_prefix(' ')?.trim();
+        if title.is_empty() {
+            return None;
+        }
+
+        Some(title.to_string())
+    }
+
+    /// Breaks a section into the units used to build chunks.
+    fn units(&self, content: &str, section: Range<usize>) -> Vec<Unit> {
+        let mut units = Vec::new();
+
+        for paragraph in Self::paragraphs(content, section) {
+            let tokens = Self::estimate_tokens(&content[paragraph.clone()]);
+            if tokens <= self.config.chunk_size {
+                units.push(Unit {
+                    range: paragraph,
+                    tokens,
+                });
+                continue;
+            }
+
+            for sentence in Self::sentences(content, paragraph) {
+                let tokens = Self::estimate_tokens(&content[sentence.clone()]);
+                if tokens <= self.config.chunk_size {
+                    units.push(Unit {
+                        range: sentence,
+                        tokens,
+                    });
+                } else {
+                    units.extend(self.word_groups(content, sentence));
+                }
+            }
+        }
+
+        units
+    }
+
+    /// Greedily groups units into chunks of at most `chunk_size` tokens, starting
+    /// each chunk with the tail of the previous one as overlap.
+    fn pack(&self, units: Vec<Unit>) -> Vec<Range<usize>> {
+        let mut chunks = Vec::new();
+        let mut current: Vec<Unit> = Vec::new();
+        let mut tokens = 0;
+        // Whether `current` holds anything that wasn't part of the last chunk.
+        let mut fresh = false;
+
+        for unit in units {
+            if fresh && tokens + unit.tokens > self.config.chunk_size {
+                chunks.push(current[0].range.start..current[current.len() - 1].range.end);
+
+                let mut kept = Vec::new();
+                let mut kept_tokens = 0;
+                for u in current.iter().rev() {
+                    if kept_tokens + u.tokens > self.config.chunk_overlap {
+                        break;
+                    }
+                    kept_tokens += u.tokens;
+                    kept.push(u.clone());
+                }
+                kept.reverse();
+
+                current = kept;
+                tokens = kept_tokens;
+            }
+
+            // Overlap never pushes a chunk past its size.
+            while !current.is_empty() && tokens + unit.tokens > self.config.chunk_size {
+                tokens -= current.remove(0).tokens;
+            }
+
+            tokens += unit.tokens;
+            current.push(unit);
+            fresh = true;
+        }
+
+        if fresh {
+            chunks.push(current[0].range.start..current[current.len() - 1].range.end);
+        }
+
+        chunks
+    }
+
+    fn paragraphs(content: &str, section: Range<usize>) -> Vec<Range<usize>> {
+        let mut paragraphs = Vec::new();
+        let mut start = None;
+        let mut offset = section.start;
+
+        for line in content[section.clone()].split_inclusive('\n') {
+            if line.trim().is_empty() {
+                if let Some(s) = start.take() {
+                    paragraphs.extend(Self::trim_range(content, s..offset));
+                }
+            } else if start.is_none() {
+                start = Some(offset);
+            }
+
+            offset += line.len();
+        }
+
+        if let Some(s) = start {
+            paragraphs.extend(Self::trim_range(content, s..section.end));
+        }
+
+        paragraphs
+    }
+
+    fn sentences(content: &str, paragraph: Range<usize>) -> Vec<Range<usize>> {
+        let text = &content[paragraph.clone()];
+        let mut sentences = Vec::new();
+        let mut start = 0;
+
+        let mut chars = text.char_indices().peekable();
+        while let Some((i, c)) = chars.next() {
+            let is_end = matches!(c, '.' | '!' | '?' | '\n');
+            let followed_by_space = chars.peek().is_some_and(|(_, next)| next.is_whitespace());
+
+            if is_end && followed_by_space {
+                let end = i + c.len_utf8();
+                sentences.extend(Self::trim_range(
+                    content,
+                    paragraph.start + start..paragraph.start + end,
+                ));
+                start = end;
+            }
+        }
+
+        sentences.extend(Self::trim_range(
+            content,
+            paragraph.start + start..paragraph.end,
+        ));
+
+        sentences
+    }
+
+    /// Last resort for sentences that are too long on their own.
+    fn word_groups(&self, content: &str, sentence: Range<usize>) -> Vec<Unit> {
+        let text = &content[sentence.clone()];
+        let words_per_group = (self.config.chunk_size * 3 / 4).max(1);
+
+        let mut groups = Vec::new();
+        let mut group_start = None;
+        let mut group_end = 0;
+        let mut count = 0;
+
+        for (offset, word) in Self::word_offsets(text) {
+            group_start.get_or_insert(offset);
+            group_end = offset + word.len();
+            count += 1;
+
+            if count == words_per_group {
+                let range =
+                    sentence.start + group_start.take().unwrap()..sentence.start + group_end;
+                groups.push(Unit {
+                    tokens: Self::estimate_tokens(&content[range.clone()]),
+                    range,
+                });
+                count = 0;
+            }
+        }
+
+        if let Some(s) = group_start {
+            let range = sentence.start + s..sentence.start + group_end;
+            groups.push(Unit {
+                tokens: Self::estimate_tokens(&content[range.clone()]),
+                range,
+            });
+        }
+
+        groups
+    }
+
+    fn word_offsets(text: &str) -> impl Iterator<Item = (usize, &str)> {
+        text.split_whitespace()
+            .map(move |word| (word.as_ptr() as usize - text.as_ptr() as usize, word))
+    }
+
+    fn trim_range(content: &str, range: Range<usize>) -> Option<Range<usize>> {
+        let text = &content[range.clone()];
+        let trimmed_start = text.trim_start();
+        let start = range.start + (text.len() - trimmed_start.len());
+        let end = start + trimmed_start.trim_end().len();
+
+        if start == end {
+            None
+        } else {
+            Some(start..end)
+        }
+    }
+}
+
+/// Converts byte offsets into character offsets. Offsets must be asked in
+/// increasing order, which lets the conversion walk the content only once.
+struct CharOffsets<'a> {
+    content: &'a str,
+    byte: usize,
+    char: usize,
+}
+
+impl<'a> CharOffsets<'a> {
+    fn new(content: &'a str) -> Self {
+        Self {
+            content,
+            byte: 0,
+            char: 0,
+        }
+    }
+
+    fn char_offset(&mut self, byte: usize) -> usize {
+        self.char += self.content[self.byte..byte].chars().count();
+        self.byte = byte;
+        self.char
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunker(chunk_size: usize, chunk_overlap: usize) -> Chunker {
+        Chunker::new(ChunkerConfig {
+            chunk_size,
+            chunk_overlap,
+        })
+    }
+
+    fn part(content: &str, text: &str, location: ChunkLocation) -> Part {
+        let start = content.find(text).unwrap();
+        Part {
+            range: start..start + text.len(),
+            location,
+        }
+    }
+
+    #[test]
+    fn headings_start_sections() {
+        let content = "Preamble.\n\n# Intro\nHello world.\n\n## Usage\nRun it.";
+        let chunks = chunker(200, 40).split(content);
+
+        let headings: Vec<_> = chunks.iter().map(|c| c.heading.as_deref()).collect();
+        assert_eq!(headings, [None, Some("Intro"), Some("Usage")]);
+        assert_eq!(chunks[1].text, "# Intro\nHello world.");
+        assert_eq!(chunks[2].text, "## Usage\nRun it.");
+    }
+
+    #[test]
+    fn packed_chunks_overlap() {
+        // Every paragraph is three words, estimated at 4 tokens.
+        let content = "a b c\n\nd e f\n\ng h i\n\nj k l";
+        let chunks = chunker(8, 4).split(content);
+
+        let texts: Vec<_> = chunks.iter().map(|c| c.text.as_str()).collect();
+        assert_eq!(
+            texts,
+            ["a b c\n\nd e f", "d e f\n\ng h i", "g h i\n\nj k l"]
+        );
+        let ordinals: Vec<_> = chunks.iter().map(|c| c.ordinal).collect();
+        assert_eq!(ordinals, [0, 1, 2]);
+    }
+
+    #[test]
+    fn no_overlap_when_disabled() {
+        let content = "a b c\n\nd e f\n\ng h i";
+        let chunks = chunker(8, 0).split(content);
+
+        let texts: Vec<_> = chunks.iter().map(|c| c.text.as_str()).collect();
+        assert_eq!(texts, ["a b c\n\nd e f", "g h i"]);
+    }
+
+    #[test]
+    fn char_offsets_count_characters() {
+        let content = "aé😀b";
+        let mut offsets = CharOffsets::new(content);
+
+        assert_eq!(offsets.char_offset(0), 0);
+        assert_eq!(offsets.char_offset(1), 1);
+        assert_eq!(offsets.char_offset(3), 2);
+        assert_eq!(offsets.char_offset(7), 3);
+        assert_eq!(offsets.char_offset(8), 4);
+    }
+
+    #[test]
+    fn chunk_offsets_are_in_characters() {
+        let content = "Ça coûte cher.\n\nÀ bientôt, été.";
+        let chunks = chunker(4, 0).split(content);
+
+        assert_eq!(chunks.len(), 2);
+        for chunk in &chunks {
+            let text: String = content
+                .chars()
+                .skip(chunk.start)
+                .take(chunk.end - chunk.start)
+                .collect();
+            assert_eq!(text, chunk.text);
+        }
+    }
+
+    #[test]
+    fn grouped_parts_merge_rows_within_a_section() {
+        let content = "r1\nr2\nr3";
+        let sheet = |name: &str, row| ChunkLocation {
+            section: Some(name.to_string()),
+            rows: Some((row, row)),
+            ..Default::default()
+        };
+        let parts = [
+            part(content, "r1", sheet("Sheet1", 1)),
+            part(content, "r2", sheet("Sheet1", 2)),
+            part(content, "r3", sheet("Sheet2", 1)),
+        ];
+
+        let chunks = chunker(200, 40).group_parts(content, &parts);
+
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[0].text, "r1\nr2");
+        assert_eq!(chunks[0].location.rows, Some((1, 2)));
+        assert_eq!(chunks[1].location.section.as_deref(), Some("Sheet2"));
+        assert_eq!(chunks[1].location.rows, Some((1, 1)));
+        assert_eq!((chunks[0].ordinal, chunks[1].ordinal), (0, 1));
+    }
+
+    #[test]
+    fn grouped_parts_merge_line_ranges() {
+        let content = "fn a() {}\n\nfn b() {}";
+        let lines = |first, last| ChunkLocation {
+            lines: Some((first, last)),
+            ..Default::default()
+        };
+        let parts = [
+            part(content, "fn a() {}", lines(1, 1)),
+            part(content, "fn b() {}", lines(3, 3)),
+        ];
+
+        let chunks = chunker(200, 40).group_parts(content, &parts);
+
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].location.lines, Some((1, 3)));
+    }
+
+    #[test]
+    fn oversized_record_pieces_get_their_own_lines() {
+        let content = "one two three\nfour five six";
+        let parts = [part(
+            content,
+            content,
+            ChunkLocation {
+                lines: Some((10, 11)),
+                ..Default::default()
+            },
+        )];
+
+        let chunks = chunker(4, 0).group_parts(content, &parts);
+
+        let texts: Vec<_> = chunks.iter().map(|c| c.text.as_str()).collect();
+        assert_eq!(texts, ["one two three", "four five six"]);
+        assert_eq!(chunks[0].location.lines, Some((10, 10)));
+        assert_eq!(chunks[1].location.lines, Some((11, 11)));
+    }
+}
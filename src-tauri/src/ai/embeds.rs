@@ -4,39 +4,84 @@ use std::{
 };
 
 use anyhow::{Context, Result};
-use arrow_array::{FixedSizeListArray, Float32Array, RecordBatch, StringArray};
+use arrow_array::{FixedSizeListArray, Float32Array, Int32Array, RecordBatch, StringArray};
 use arrow_schema::{DataType, Field, Schema};
 use fastembed::{EmbeddingModel, InitOptions, TextEmbedding};
+use serde::{Deserialize, Serialize};
 
-pub struct EmbedModel {
-    model: TextEmbedding,
-    pub schema: Arc<Schema>,
+use crate::{
+    ai::{
+        chunker::{Chunk, Chunker, ChunkerConfig},
+        extractors::ExtractedDocument,
+    },
+    db::settings::Settings,
+};
+
+/// Embedding models that can be picked in the settings. They all produce 384 dimension
+/// vectors, which is what the LanceDB table is created with.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum EmbeddingModelKind {
+    #[default]
+    AllMiniLmL6V2,
+    ParaphraseMultilingualMiniLmL12V2,
+    MultilingualE5Small,
+    BgeSmallEnV15,
 }
 
-pub struct ProcessedDocument {
-    pub path: String,
-    pub raw_content: String,
-    pub chunk_count: usize,
-    pub batch: RecordBatch,
+impl EmbeddingModelKind {
+    fn to_fastembed(self) -> EmbeddingModel {
+        match self {
+            Self::AllMiniLmL6V2 => EmbeddingModel::AllMiniLML6V2,
+            Self::ParaphraseMultilingualMiniLmL12V2 => EmbeddingModel::ParaphraseMLMiniLML12V2,
+            Self::MultilingualE5Small => EmbeddingModel::MultilingualE5Small,
+            Self::BgeSmallEnV15 => EmbeddingModel::BGESmallENV15,
+        }
+    }
+
+    /// Largest chunk the model reads whole, in estimated tokens. Past the length the
+    /// model was trained on, the end of a chunk is silently cut off. Estimates are
+    /// rough, so this stays below the real limit.
+    pub fn max_chunk_tokens(self) -> usize {
+        match self {
+            Self::AllMiniLmL6V2 => 256,
+            Self::ParaphraseMultilingualMiniLmL12V2 => 128,
+            Self::MultilingualE5Small | Self::BgeSmallEnV15 => 384,
+        }
+    }
 }
 
-const BATCH_SIZE: usize = 32;
+pub struct EmbedModel {
+    model: TextEmbedding,
+    kind: EmbeddingModelKind,
+    batch_size: usize,
+    chunker: Chunker,
+    cache_dir: PathBuf,
+    pub schema: Arc<Schema>,
+}
 
 impl EmbedModel {
-    pub fn new(app_data_dir: PathBuf) -> Result<Self> {
+    pub fn new(app_data_dir: PathBuf, settings: &Settings) -> Result<Self> {
         let cache_dir = app_data_dir.join("fastembed_cache");
+        let kind = settings.embedding_model;
+        let model = Self::load_model(&cache_dir, kind)?;
 
-        let options = InitOptions::new(EmbeddingModel::AllMiniLML6V2).with_cache_dir(cache_dir);
-
-        let model =
-            TextEmbedding::try_new(options).context("Failed to initialize FastEmbed model.")?;
-
-        let dim = 384;
+        let dim = TextEmbedding::get_model_info(&kind.to_fastembed())?.dim as i32;
         let schema = Arc::new(Schema::new(vec![
             Field::new("attachment_id", DataType::Utf8, false),
             Field::new("notebook_id", DataType::Utf8, false),
             Field::new("path", DataType::Utf8, false),
             Field::new("text", DataType::Utf8, false),
+            Field::new("chunk_index", DataType::Int32, true),
+            Field::new("start_offset", DataType::Int32, true),
+            Field::new("end_offset", DataType::Int32, true),
+            Field::new("heading", DataType::Utf8, true),
+            Field::new("section", DataType::Utf8, true),
+            Field::new("row_start", DataType::Int32, true),
+            Field::new("row_end", DataType::Int32, true),
+            Field::new("line_start", DataType::Int32, true),
+            Field::new("line_end", DataType::Int32, true),
+            Field::new("page", DataType::Int32, true),
             Field::new(
                 "vector",
                 DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Float32, true)), dim),
@@ -44,7 +89,48 @@ impl EmbedModel {
             ),
         ]));
 
-        Ok(Self { model, schema })
+        Ok(Self {
+            model,
+            kind,
+            batch_size: settings.batch_size,
+            chunker: Chunker::new(Self::chunker_config(settings)),
+            cache_dir,
+            schema,
+        })
+    }
+
+    /// Loads the model `settings` ask for, or returns `None` when it's already the one
+    /// in use. Nothing is changed yet, so a failed download leaves the model as it was.
+    pub fn load_for(&self, settings: &Settings) -> Result<Option<TextEmbedding>> {
+        if settings.embedding_model == self.kind {
+            return Ok(None);
+        }
+
+        Self::load_model(&self.cache_dir, settings.embedding_model).map(Some)
+    }
+
+    /// Applies new settings, along with the model `load_for` returned for them.
+    pub fn configure(&mut self, settings: &Settings, model: Option<TextEmbedding>) {
+        if let Some(model) = model {
+            self.model = model;
+            self.kind = settings.embedding_model;
+        }
+
+        self.batch_size = settings.batch_size;
+        self.chunker = Chunker::new(Self::chunker_config(settings));
+    }
+
+    fn chunker_config(settings: &Settings) -> ChunkerConfig {
+        ChunkerConfig {
+            chunk_size: settings.chunk_size,
+            chunk_overlap: settings.chunk_overlap,
+        }
+    }
+
+    fn load_model(cache_dir: &Path, kind: EmbeddingModelKind) -> Result<TextEmbedding> {
+        let options = InitOptions::new(kind.to_fastembed()).with_cache_dir(cache_dir.to_path_buf());
+
+        TextEmbedding::try_new(options).context("Failed to initialize FastEmbed model.")
     }
 
     pub async fn generate_from_text(&mut self, query: &str) -> Result<Vec<f32>> {
@@ -56,61 +142,80 @@ impl EmbedModel {
         Ok(query_vector)
     }
 
-    pub async fn generate_from_file(
-        &mut self,
-        file_path: &str,
-        notebook_id: &str,
-        attachment_id: &str,
-    ) -> Result<ProcessedDocument> {
-        let extension = std::path::Path::new(file_path)
-            .extension()
-            .and_then(|s| s.to_str())
-            .unwrap_or("");
-
-        let content = match extension.to_lowercase().as_str() {
-            "pdf" => self.extract_content_from_pdf(file_path)?,
-            "md" | "txt" => self.extract_content_from_plain_text(file_path)?,
-            _ => anyhow::bail!("Unsupported file format: .{}", extension),
-        };
-
-        let batch = self
-            .generate_embeddings(&file_path, &content, notebook_id, attachment_id)
-            .await?;
-
-        Ok(ProcessedDocument {
-            path: file_path.to_string(),
-            raw_content: content,
-            chunk_count: 0,
-            batch: batch,
-        })
+    pub fn chunk(&self, document: &ExtractedDocument) -> Vec<Chunk> {
+        if document.parts.is_empty() {
+            self.chunker.split(&document.content)
+        } else if document.group_parts {
+            self.chunker.group_parts(&document.content, &document.parts)
+        } else {
+            self.chunker.split_parts(&document.content, &document.parts)
+        }
+    }
+
+    pub fn batch_size(&self) -> usize {
+        self.batch_size
     }
 
-    async fn generate_embeddings(
-        &mut self,
+    /// Embeds a single batch of texts. Callers split big documents in batches of
+    /// `batch_size` so the model isn't held for the whole document.
+    pub fn embed_batch(&mut self, texts: &[&str]) -> Result<Vec<Vec<f32>>> {
+        self.model
+            .embed(texts.to_vec(), None)
+            .context("There was an error while trying to generate embeddings for this file.")
+    }
+
+    /// Builds the LanceDB rows of a document from its chunks and their embeddings.
+    pub fn build_record_batch(
+        &self,
         file_path: &str,
-        file_content: &str,
         notebook_id: &str,
         attachment_id: &str,
+        chunks: &[Chunk],
+        embeddings: Vec<Vec<f32>>,
     ) -> Result<RecordBatch> {
-        let chunks: Vec<&str> = file_content
-            .split("\n\n")
-            .filter(|s| !s.trim().is_empty())
-            .collect();
+        let texts: Vec<&str> = chunks.iter().map(|c| c.text.as_str()).collect();
 
-        let embeddings: Vec<Vec<f32>> = chunks
-            .chunks(BATCH_SIZE)
-            .flat_map(|batch| self.model.embed(batch.to_vec(), None).unwrap())
-            .collect();
-
-        let vector = embeddings[0].clone();
-        let dim = vector.len() as i32;
+        let dim = embeddings
+            .first()
+            .context("There are no embeddings to store.")?
+            .len() as i32;
 
         // Each chunk needs to know which file it came from.
         //
         let path_array = StringArray::from(vec![file_path; chunks.len()]);
         let nb_id_array = StringArray::from(vec![notebook_id; chunks.len()]);
         let att_id_array = StringArray::from(vec![attachment_id; chunks.len()]);
-        let text_array = StringArray::from(chunks);
+        let text_array = StringArray::from(texts);
+
+        // Where the chunk sits in the document, so neighbours can be found later.
+        let index_array: Int32Array = chunks.iter().map(|c| Some(c.ordinal as i32)).collect();
+        let start_array: Int32Array = chunks.iter().map(|c| Some(c.start as i32)).collect();
+        let end_array: Int32Array = chunks.iter().map(|c| Some(c.end as i32)).collect();
+        let heading_array: StringArray = chunks.iter().map(|c| c.heading.as_deref()).collect();
+        let section_array: StringArray = chunks
+            .iter()
+            .map(|c| c.location.section.as_deref())
+            .collect();
+        let row_start_array: Int32Array = chunks
+            .iter()
+            .map(|c| c.location.rows.map(|(start, _)| start as i32))
+            .collect();
+        let row_end_array: Int32Array = chunks
+            .iter()
+            .map(|c| c.location.rows.map(|(_, end)| end as i32))
+            .collect();
+        let line_start_array: Int32Array = chunks
+            .iter()
+            .map(|c| c.location.lines.map(|(start, _)| start as i32))
+            .collect();
+        let line_end_array: Int32Array = chunks
+            .iter()
+            .map(|c| c.location.lines.map(|(_, end)| end as i32))
+            .collect();
+        let page_array: Int32Array = chunks
+            .iter()
+            .map(|c| c.location.page.map(|p| p as i32))
+            .collect();
 
         // Now we flat the embeddings into a continuos memory block.
         // Then we turn it into a float 32 array to make it more efficient (there are probably some drawbacks but who cares).
@@ -133,24 +238,20 @@ impl EmbedModel {
                 Arc::new(nb_id_array),
                 Arc::new(path_array),
                 Arc::new(text_array),
+                Arc::new(index_array),
+                Arc::new(start_array),
+                Arc::new(end_array),
+                Arc::new(heading_array),
+                Arc::new(section_array),
+                Arc::new(row_start_array),
+                Arc::new(row_end_array),
+                Arc::new(line_start_array),
+                Arc::new(line_end_array),
+                Arc::new(page_array),
                 Arc::new(vector_array),
             ],
         )?;
 
         Ok(batch)
     }
-
-    fn extract_content_from_pdf<P: AsRef<Path>>(&self, path: P) -> Result<String> {
-        let content =
-            pdf_extract::extract_text(path).context("Failed to extract text from PDF file")?;
-
-        Ok(content)
-    }
-
-    fn extract_content_from_plain_text<P: AsRef<Path>>(&self, path: P) -> Result<String> {
-        let content =
-            std::fs::read_to_string(&path).context("Failed to read content from file.")?;
-
-        Ok(content)
-    }
 }
@@ -0,0 +1,173 @@
+use anyhow::{Context, Result};
+use regex::Regex;
+
+use super::ExtractedDocument;
+use crate::{
+    ai::chunker::{ChunkLocation, Part},
+    db::attachments::AttachmentMetadata,
+};
+
+/// How to find the top-level definitions of a language. Patterns are matched
+/// against lines that aren't indented and capture the `name` of the definition,
+/// and its `kind` when the language spells it out.
+struct Language {
+    extensions: &'static [&'static str],
+    definitions: &'static [&'static str],
+}
+
+const LANGUAGES: &[Language] = &[
+    Language {
+        extensions: &["rs"],
+        definitions: &[
+            r#"^(?:pub(?:\([^)]*\))?\s+)?(?:(?:async|const|unsafe|extern\s+"[^"]*")\s+)*(?P<kind>fn|struct|enum|trait|union|mod|type|static|const|macro_rules!)\s*(?P<name>[A-Za-z_]\w*)"#,
+            r"^(?:unsafe\s+)?(?P<kind>impl)(?:<[^{]*?>)?\s+(?P<name>[^{]+?)\s*(?:\{.*)?$",
+        ],
+    },
+    Language {
+        extensions: &["py", "pyi"],
+        definitions: &[r"^(?:async\s+)?(?P<kind>def|class)\s+(?P<name>\w+)"],
+    },
+    Language {
+        extensions: &["ts", "tsx", "js", "jsx", "mjs", "cjs"],
+        definitions: &[
+            r"^(?:export\s+)?(?:default\s+)?(?:declare\s+)?(?:abstract\s+)?(?:async\s+)?(?P<kind>function\*?|class|interface|enum|type|namespace)\s+(?P<name>[\w$]+)",
+            r"^(?:export\s+)?(?P<kind>const|let|var)\s+(?P<name>[\w$]+)\s*(?::[^=]*)?=\s*(?:async\s+)?(?:function|\([^)]*\)\s*(?::[^=]*)?=>|[\w$]+\s*=>)",
+        ],
+    },
+    Language {
+        extensions: &["go"],
+        definitions: &[
+            r"^(?P<kind>func)\s+(?:\([^)]*\)\s*)?(?P<name>\w+)",
+            r"^(?P<kind>type)\s+(?P<name>\w+)",
+        ],
+    },
+    Language {
+        extensions: &["java", "kt", "kts", "scala", "cs", "swift"],
+        definitions: &[
+            r"^(?:@\w+\s+)*(?:(?:public|private|protected|internal|abstract|final|sealed|static|open|data|partial|case)\s+)*(?P<kind>class|interface|enum|record|struct|object|trait|fun|func|def|extension)\s+(?P<name>\w+)",
+        ],
+    },
+    Language {
+        extensions: &["rb"],
+        definitions: &[r"^(?P<kind>def|class|module)\s+(?P<name>[\w:.?!]+)"],
+    },
+    Language {
+        extensions: &["php"],
+        definitions: &[
+            r"^(?:(?:abstract|final)\s+)?(?P<kind>function|class|interface|trait|enum)\s+(?P<name>\w+)",
+        ],
+    },
+    Language {
+        extensions: &["c", "h", "cpp", "hpp", "cc", "cxx"],
+        definitions: &[
+            // A return type, then the name and its parameters, without the `;` of a
+            // declaration.
+            r"^(?:[A-Za-z_][\w:]*(?:\s*<[^>]*>)?[*&]*[\s*&]+)+(?P<name>[A-Za-z_][\w:~]*)\s*\([^;]*$",
+            r"^(?:typedef\s+)?(?P<kind>struct|class|enum|union|namespace)\s+(?P<name>\w+)",
+        ],
+    },
+];
+
+/// Comments, attributes and decorators right above a definition belong to it.
+const PREFIX_MARKERS: &[&str] = &["//", "/*", "*", "#", "@", "--"];
+
+pub fn extensions() -> impl Iterator<Item = &'static str> {
+    LANGUAGES.iter().flat_map(|l| l.extensions.iter().copied())
+}
+
+/// Splits a source file into its top-level definitions, each one a part labelled
+/// with its symbol and line range. Code before the first definition, like imports,
+/// is a part of its own.
+pub fn extract(path: &str, extension: &str) -> Result<ExtractedDocument> {
+    let content = std::fs::read_to_string(path).context("Failed to read content from file.")?;
+
+    let language = LANGUAGES
+        .iter()
+        .find(|l| l.extensions.contains(&extension))
+        .with_context(|| format!("Unsupported file format: .{}", extension))?;
+
+    let patterns = language
+        .definitions
+        .iter()
+        .map(|p| Regex::new(p))
+        .collect::<Result<Vec<_>, _>>()
+        .context("Invalid definition pattern")?;
+
+    // Byte offset where each line starts, plus the end of the content.
+    let mut line_starts = vec![0];
+    line_starts.extend(content.match_indices('\n').map(|(i, _)| i + 1));
+    if *line_starts.last().unwrap() != content.len() {
+        line_starts.push(content.len());
+    }
+    let lines: Vec<&str> = (0..line_starts.len() - 1)
+        .map(|i| &content[line_starts[i]..line_starts[i + 1]])
+        .collect();
+
+    // Where every definition starts, as a line index, with its symbol.
+    let mut starts: Vec<(usize, Option<String>)> = vec![(0, None)];
+    for (i, line) in lines.iter().enumerate() {
+        let Some(symbol) = patterns.iter().find_map(|p| symbol(p, line)) else {
+            continue;
+        };
+
+        let mut start = i;
+        let previous_start = starts.last().map(|(s, _)| *s).unwrap_or(0);
+        while start > previous_start && is_prefix(lines[start - 1]) {
+            start -= 1;
+        }
+
+        if start == previous_start {
+            // Nothing but comments since the last definition, e.g. the start of the file.
+            starts.pop();
+        }
+        starts.push((start, Some(symbol)));
+    }
+
+    let mut parts = Vec::new();
+    for (k, (start, symbol)) in starts.iter().enumerate() {
+        let next = starts.get(k + 1).map(|(s, _)| *s).unwrap_or(lines.len());
+
+        // Blank lines between definitions don't belong to either of them.
+        let mut end = next;
+        while end > *start && lines[end - 1].trim().is_empty() {
+            end -= 1;
+        }
+        let mut first = *start;
+        while first < end && lines[first].trim().is_empty() {
+            first += 1;
+        }
+
+        if first == end {
+            continue;
+        }
+
+        parts.push(Part {
+            range: line_starts[first]..line_starts[end - 1] + lines[end - 1].trim_end().len(),
+            location: ChunkLocation {
+                section: symbol.clone(),
+                lines: Some((first + 1, end)),
+                ..Default::default()
+            },
+        });
+    }
+
+    Ok(ExtractedDocument {
+        content,
+        metadata: AttachmentMetadata::default(),
+        parts,
+        group_parts: true,
+    })
+}
+
+fn symbol(pattern: &Regex, line: &str) -> Option<String> {
+    let captures = pattern.captures(line.trim_end())?;
+    let kind = captures.name("kind").map_or("function", |k| k.as_str());
+    let name = captures.name("name")?.as_str().trim();
+
+    Some(format!("{} {}", kind, name))
+}
+
+fn is_prefix(line: &str) -> bool {
+    let trimmed = line.trim_start();
+    !trimmed.is_empty() && PREFIX_MARKERS.iter().any(|m| trimmed.starts_with(m))
+}
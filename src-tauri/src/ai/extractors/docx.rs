@@ -0,0 +1,308 @@
+use std::collections::HashMap;
+
+use anyhow::{Context, Result};
+use quick_xml::{
+    events::{BytesStart, Event},
+    Reader,
+};
+
+use super::{attribute, open_zip, read_zip_entry, TextBuilder};
+
+/// Extracts the text of a Word document, with its footnotes at the end.
+pub fn extract(path: &str) -> Result<String> {
+    let mut archive = open_zip(path)?;
+
+    let document = read_zip_entry(&mut archive, "word/document.xml")?
+        .context("This Word document has no content.")?;
+
+    let styles = match read_zip_entry(&mut archive, "word/styles.xml")? {
+        Some(styles) => heading_styles(&styles)?,
+        None => HashMap::new(),
+    };
+
+    let mut builder = TextBuilder::default();
+    parse(&document, &styles, &mut builder)?;
+
+    if let Some(footnotes) = read_zip_entry(&mut archive, "word/footnotes.xml")? {
+        let mut notes = TextBuilder::default();
+        parse(&footnotes, &styles, &mut notes)?;
+
+        let notes = notes.finish();
+        if !notes.is_empty() {
+            builder.heading(1, "Footnotes");
+            builder.paragraph(&notes);
+        }
+    }
+
+    Ok(builder.finish())
+}
+
+#[derive(Default)]
+struct Paragraph {
+    text: String,
+    heading: Option<usize>,
+    list_item: bool,
+}
+
+/// Walks the body of `document.xml` or `footnotes.xml`, they share the same markup.
+fn parse(xml: &str, styles: &HashMap<String, usize>, builder: &mut TextBuilder) -> Result<()> {
+    let mut reader = Reader::from_str(xml);
+
+    let mut paragraph = Paragraph::default();
+    let mut in_text = false;
+    // Set when entering a footnote, used as a prefix for its first paragraph.
+    let mut footnote: Option<String> = None;
+
+    // Nested tables are flattened into the cells of the outermost one.
+    let mut table_depth = 0;
+    let mut rows: Vec<Vec<String>> = Vec::new();
+    let mut cell: Option<String> = None;
+
+    loop {
+        match reader
+            .read_event()
+            .context("Failed to parse the Word document.")?
+        {
+            Event::Start(e) => {
+                match e.name().as_ref() {
+                    b"w:p" => paragraph = Paragraph::default(),
+                    b"w:t" => in_text = true,
+                    b"w:tbl" => {
+                        table_depth += 1;
+                        if table_depth == 1 {
+                            rows.clear();
+                        }
+                    }
+                    b"w:tr" if table_depth == 1 => rows.push(Vec::new()),
+                    b"w:tc" if table_depth == 1 => cell = Some(String::new()),
+                    b"w:footnote" => footnote = attribute(&e, "w:id"),
+                    _ => {}
+                }
+                on_element(&e, styles, &mut paragraph);
+            }
+            Event::Empty(e) => on_element(&e, styles, &mut paragraph),
+            Event::Text(t) if in_text => {
+                paragraph
+                    .text
+                    .push_str(&t.unescape().context("Invalid text in the Word document.")?);
+            }
+            Event::End(e) => match e.name().as_ref() {
+                b"w:t" => in_text = false,
+                b"w:p" => {
+                    let mut text = paragraph.text.trim().to_string();
+                    if text.is_empty() {
+                        continue;
+                    }
+
+                    if let Some(id) = footnote.take() {
+                        text = format!("[^{}]: {}", id, text);
+                    }
+
+                    match (cell.as_mut(), paragraph.heading) {
+                        (Some(cell), _) => {
+                            cell.push(' ');
+                            cell.push_str(&text);
+                        }
+                        (None, Some(level)) => builder.heading(level, &text),
+                        (None, None) if paragraph.list_item => builder.list_item(&text),
+                        (None, None) => builder.paragraph(&text),
+                    }
+                }
+                b"w:tc" if table_depth == 1 => {
+                    if let (Some(text), Some(row)) = (cell.take(), rows.last_mut()) {
+                        row.push(text.trim().to_string());
+                    }
+                }
+                b"w:tbl" => {
+                    table_depth -= 1;
+                    if table_depth == 0 {
+                        builder.table(&rows);
+                    }
+                }
+                _ => {}
+            },
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// Handles the elements that only matter for their attributes or position,
+/// whether they are written as empty tags or not.
+fn on_element(e: &BytesStart, styles: &HashMap<String, usize>, paragraph: &mut Paragraph) {
+    match e.name().as_ref() {
+        b"w:pStyle" => {
+            let level = attribute(e, "w:val")
+                .and_then(|s| styles.get(&s).copied().or_else(|| heading_level(&s)));
+            if let Some(level) = level {
+                paragraph.heading = Some(level);
+            }
+        }
+        b"w:outlineLvl" => {
+            // Outline levels start at 0 and 9 means body text.
+            if let Some(level) = attribute(e, "w:val").and_then(|v| v.parse::<usize>().ok()) {
+                if level < 9 {
+                    paragraph.heading = Some(level + 1);
+                }
+            }
+        }
+        b"w:numPr" => paragraph.list_item = true,
+        b"w:tab" => paragraph.text.push('\t'),
+        b"w:br" | b"w:cr" => paragraph.text.push('\n'),
+        b"w:footnoteReference" => {
+            if let Some(id) = attribute(e, "w:id") {
+                paragraph.text.push_str(&format!("[^{}]", id));
+            }
+        }
+        _ => {}
+    }
+}
+
+#[derive(Default)]
+struct Style {
+    name: Option<String>,
+    outline_level: Option<usize>,
+    based_on: Option<String>,
+}
+
+/// Heading level of the paragraph styles of `styles.xml`, by style id. Localized
+/// versions of Word translate the ids, so levels come from the outline level or
+/// the built-in name of a style, or of the style it's based on.
+fn heading_styles(xml: &str) -> Result<HashMap<String, usize>> {
+    let mut reader = Reader::from_str(xml);
+    let mut styles: HashMap<String, Style> = HashMap::new();
+    let mut current: Option<(String, Style)> = None;
+
+    loop {
+        match reader
+            .read_event()
+            .context("Failed to parse the Word document styles.")?
+        {
+            Event::Start(e) => match e.name().as_ref() {
+                b"w:style" => {
+                    current = attribute(&e, "w:styleId")
+                        .filter(|_| attribute(&e, "w:type").as_deref() == Some("paragraph"))
+                        .map(|id| (id, Style::default()));
+                }
+                _ => on_style_element(&e, &mut current),
+            },
+            Event::Empty(e) => on_style_element(&e, &mut current),
+            Event::End(e) if e.name().as_ref() == b"w:style" => {
+                if let Some((id, style)) = current.take() {
+                    styles.insert(id, style);
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(styles
+        .keys()
+        .filter_map(|id| Some((id.clone(), style_level(&styles, id)?)))
+        .collect())
+}
+
+fn on_style_element(e: &BytesStart, current: &mut Option<(String, Style)>) {
+    let Some((_, style)) = current else {
+        return;
+    };
+
+    match e.name().as_ref() {
+        b"w:name" => style.name = attribute(e, "w:val"),
+        b"w:basedOn" => style.based_on = attribute(e, "w:val"),
+        b"w:outlineLvl" => {
+            style.outline_level = attribute(e, "w:val").and_then(|v| v.parse().ok());
+        }
+        _ => {}
+    }
+}
+
+fn style_level(styles: &HashMap<String, Style>, id: &str) -> Option<usize> {
+    let mut id = id;
+
+    // The limit only guards against `basedOn` cycles.
+    for _ in 0..10 {
+        let style = styles.get(id)?;
+
+        if let Some(level) = style.outline_level {
+            // 9 means body text.
+            return (level < 9).then_some(level + 1);
+        }
+
+        let name = style.name.as_deref().map(str::to_lowercase);
+        match name.as_deref() {
+            Some("title") => return Some(1),
+            Some(name) => {
+                if let Some(level) = name
+                    .strip_prefix("heading ")
+                    .and_then(|n| n.parse::<usize>().ok())
+                {
+                    return Some(level);
+                }
+            }
+            None => {}
+        }
+
+        id = style.based_on.as_deref()?;
+    }
+
+    None
+}
+
+/// Level of the built-in heading styles, `Title` counting as the first level. Used
+/// when the document has no styles to look the id up in.
+fn heading_level(style: &str) -> Option<usize> {
+    if style == "Title" {
+        return Some(1);
+    }
+
+    style
+        .strip_prefix("Heading")
+        .and_then(|n| n.parse::<usize>().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STYLES: &str = r#"<w:styles xmlns:w="http://schemas.openxmlformats.org/wordprocessingml/2006/main">
+        <w:style w:type="paragraph" w:default="1" w:styleId="Normal">
+            <w:name w:val="Normal"/>
+        </w:style>
+        <w:style w:type="paragraph" w:styleId="Ttulo1">
+            <w:name w:val="heading 1"/>
+            <w:basedOn w:val="Normal"/>
+            <w:pPr><w:outlineLvl w:val="0"/></w:pPr>
+        </w:style>
+        <w:style w:type="paragraph" w:styleId="Ttulo2">
+            <w:name w:val="heading 2"/>
+            <w:basedOn w:val="Normal"/>
+        </w:style>
+        <w:style w:type="paragraph" w:styleId="Capitulo">
+            <w:name w:val="Capitulo"/>
+            <w:pPr><w:outlineLvl w:val="2"/></w:pPr>
+        </w:style>
+        <w:style w:type="paragraph" w:styleId="MeuTitulo">
+            <w:name w:val="Meu titulo"/>
+            <w:basedOn w:val="Ttulo1"/>
+        </w:style>
+        <w:style w:type="character" w:styleId="Ttulo1Char">
+            <w:name w:val="heading 1 Char"/>
+        </w:style>
+    </w:styles>"#;
+
+    #[test]
+    fn localized_and_custom_heading_styles() {
+        let styles = heading_styles(STYLES).unwrap();
+
+        assert_eq!(styles.get("Ttulo1"), Some(&1));
+        assert_eq!(styles.get("Ttulo2"), Some(&2));
+        assert_eq!(styles.get("Capitulo"), Some(&3));
+        assert_eq!(styles.get("MeuTitulo"), Some(&1));
+        assert_eq!(styles.get("Normal"), None);
+        assert_eq!(styles.get("Ttulo1Char"), None);
+    }
+}
@@ -0,0 +1,289 @@
+use std::{collections::HashMap, fs::File};
+
+use anyhow::{Context, Result};
+use quick_xml::{events::Event, Reader};
+use scraper::{Html, Selector};
+use zip::ZipArchive;
+
+use super::{attribute, collapse_whitespace, html, open_zip, read_zip_entry, ExtractedDocument};
+use crate::{
+    ai::chunker::{ChunkLocation, Part},
+    db::attachments::AttachmentMetadata,
+};
+
+struct Package {
+    title: Option<String>,
+    /// Archive paths of the chapters, in reading order.
+    spine: Vec<String>,
+    /// Archive paths of the table of contents, the EPUB 3 one first.
+    tables_of_contents: Vec<String>,
+}
+
+/// Extracts the chapters of an e-book in reading order. Each chapter is a part of
+/// the document, labelled with its title from the table of contents.
+pub fn extract(path: &str) -> Result<ExtractedDocument> {
+    let mut archive = open_zip(path)?;
+
+    let container = read_zip_entry(&mut archive, "META-INF/container.xml")?
+        .context("This e-book has no container file.")?;
+    let package_path = package_path(&container)?;
+
+    let package =
+        read_zip_entry(&mut archive, &package_path)?.context("This e-book has no package file.")?;
+    let package = parse_package(&package, &package_path)?;
+
+    let mut titles = HashMap::new();
+    for toc in &package.tables_of_contents {
+        if let Some(source) = read_zip_entry(&mut archive, toc)? {
+            for (file, label) in table_of_contents(&source, toc) {
+                titles.entry(file).or_insert(label);
+            }
+        }
+    }
+
+    let mut content = String::new();
+    let mut parts = Vec::new();
+
+    for chapter in &package.spine {
+        let text = chapter_text(&mut archive, chapter)?;
+        if text.is_empty() {
+            continue;
+        }
+
+        if !content.is_empty() {
+            content.push_str("\n\n");
+        }
+
+        let start = content.len();
+        content.push_str(&text);
+
+        let section = titles
+            .get(chapter)
+            .cloned()
+            .or_else(|| first_heading(&text));
+
+        parts.push(Part {
+            range: start..content.len(),
+            location: ChunkLocation {
+                section,
+                ..Default::default()
+            },
+        });
+    }
+
+    Ok(ExtractedDocument {
+        content,
+        metadata: AttachmentMetadata {
+            title: package.title,
+            url: None,
+        },
+        parts,
+        group_parts: false,
+    })
+}
+
+fn chapter_text(archive: &mut ZipArchive<File>, path: &str) -> Result<String> {
+    let Some(source) = read_zip_entry(archive, path)? else {
+        return Ok(String::new());
+    };
+
+    Ok(html::readable_text(&Html::parse_document(&source)))
+}
+
+fn package_path(container: &str) -> Result<String> {
+    let mut reader = Reader::from_str(container);
+
+    loop {
+        match reader
+            .read_event()
+            .context("Failed to parse the e-book container.")?
+        {
+            Event::Start(e) | Event::Empty(e) if e.local_name().as_ref() == b"rootfile" => {
+                if let Some(path) = attribute(&e, "full-path") {
+                    return Ok(path);
+                }
+            }
+            Event::Eof => anyhow::bail!("The e-book container doesn't point to a package file."),
+            _ => {}
+        }
+    }
+}
+
+fn parse_package(xml: &str, package_path: &str) -> Result<Package> {
+    let mut reader = Reader::from_str(xml);
+
+    let mut title = None;
+    let mut in_title = false;
+    // Manifest items by id: path and whether it's the EPUB 3 navigation document.
+    let mut manifest: HashMap<String, (String, bool)> = HashMap::new();
+    let mut spine_ids = Vec::new();
+    let mut ncx_id = None;
+
+    loop {
+        match reader
+            .read_event()
+            .context("Failed to parse the e-book package.")?
+        {
+            Event::Start(e) | Event::Empty(e) => match e.local_name().as_ref() {
+                b"title" if title.is_none() => in_title = true,
+                b"item" => {
+                    if let (Some(id), Some(href)) = (attribute(&e, "id"), attribute(&e, "href")) {
+                        let is_nav = attribute(&e, "properties")
+                            .is_some_and(|p| p.split_whitespace().any(|p| p == "nav"));
+                        manifest.insert(id, (resolve(package_path, &href), is_nav));
+                    }
+                }
+                b"itemref" => {
+                    // Non-linear items, like footnote pages, are outside the reading order.
+                    if attribute(&e, "linear").as_deref() != Some("no") {
+                        spine_ids.extend(attribute(&e, "idref"));
+                    }
+                }
+                b"spine" => ncx_id = attribute(&e, "toc"),
+                _ => {}
+            },
+            Event::Text(t) if in_title => {
+                let text = collapse_whitespace(&t.unescape()?);
+                if !text.is_empty() {
+                    title = Some(text);
+                }
+            }
+            Event::End(e) if e.local_name().as_ref() == b"title" => in_title = false,
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    let mut tables_of_contents: Vec<String> = manifest
+        .values()
+        .filter(|(_, is_nav)| *is_nav)
+        .map(|(path, _)| path.clone())
+        .collect();
+    if let Some((path, _)) = ncx_id.and_then(|id| manifest.get(&id)) {
+        tables_of_contents.push(path.clone());
+    }
+
+    let spine = spine_ids
+        .iter()
+        .filter_map(|id| manifest.get(id))
+        .map(|(path, _)| path.clone())
+        .collect();
+
+    Ok(Package {
+        title,
+        spine,
+        tables_of_contents,
+    })
+}
+
+/// Chapter titles by archive path, read from an EPUB 3 navigation document or
+/// an EPUB 2 NCX file. The first entry pointing to a file wins.
+fn table_of_contents(source: &str, toc_path: &str) -> Vec<(String, String)> {
+    if toc_path.ends_with(".ncx") {
+        return ncx_entries(source, toc_path);
+    }
+
+    let document = Html::parse_document(source);
+    let links = Selector::parse("nav a[href]").expect("Invalid built-in selector");
+
+    document
+        .select(&links)
+        .filter_map(|a| {
+            let href = a.value().attr("href")?;
+            let label = collapse_whitespace(&a.text().collect::<String>());
+            (!label.is_empty()).then(|| (resolve(toc_path, href), label))
+        })
+        .collect()
+}
+
+fn ncx_entries(source: &str, toc_path: &str) -> Vec<(String, String)> {
+    let mut reader = Reader::from_str(source);
+    let mut entries = Vec::new();
+
+    let mut in_label = false;
+    let mut label = String::new();
+
+    loop {
+        let Ok(event) = reader.read_event() else {
+            break;
+        };
+
+        match event {
+            Event::Start(e) if e.local_name().as_ref() == b"navLabel" => {
+                in_label = true;
+                label.clear();
+            }
+            Event::End(e) if e.local_name().as_ref() == b"navLabel" => in_label = false,
+            Event::Text(t) if in_label => {
+                if let Ok(text) = t.unescape() {
+                    label.push_str(&text);
+                }
+            }
+            Event::Start(e) | Event::Empty(e) if e.local_name().as_ref() == b"content" => {
+                let label = collapse_whitespace(&label);
+                if let (Some(src), false) = (attribute(&e, "src"), label.is_empty()) {
+                    entries.push((resolve(toc_path, &src), label));
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    entries
+}
+
+fn first_heading(text: &str) -> Option<String> {
+    text.lines()
+        .find(|line| line.starts_with('#'))
+        .map(|line| line.trim_start_matches('#').trim().to_string())
+        .filter(|heading| !heading.is_empty())
+}
+
+/// Turns a link found in `base` into the archive path it points to. Fragments are
+/// dropped, since chapters are read as whole files.
+fn resolve(base: &str, href: &str) -> String {
+    let href = href.split('#').next().unwrap_or("");
+    let href = percent_decode(href);
+
+    let mut segments: Vec<&str> = base.split('/').collect();
+    segments.pop();
+
+    for segment in href.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop();
+            }
+            _ => segments.push(segment),
+        }
+    }
+
+    segments.join("/")
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = text
+            .get(i + 1..i + 3)
+            .filter(|_| bytes[i] == b'%')
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+
+        match hex {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&decoded).to_string()
+}
@@ -0,0 +1,251 @@
+use anyhow::{Context, Result};
+use scraper::{node::Node, ElementRef, Html, Selector};
+
+use super::{collapse_whitespace, ExtractedDocument, TextBuilder};
+use crate::db::attachments::AttachmentMetadata;
+
+/// Elements that never hold the content of a page.
+const SKIPPED_TAGS: &[&str] = &[
+    "script", "style", "noscript", "template", "svg", "canvas", "iframe", "object", "form",
+    "button", "select", "nav", "footer", "aside", "menu", "dialog",
+];
+
+/// Words that give away navigation and ads when used in a class or id.
+const BOILERPLATE_HINTS: &[&str] = &[
+    "nav",
+    "navbar",
+    "menu",
+    "sidebar",
+    "footer",
+    "breadcrumb",
+    "breadcrumbs",
+    "cookie",
+    "banner",
+    "advert",
+    "ads",
+    "share",
+    "social",
+    "related",
+    "comments",
+    "popup",
+    "newsletter",
+];
+
+const BLOCK_TAGS: &[&str] = &[
+    "p",
+    "div",
+    "section",
+    "article",
+    "main",
+    "blockquote",
+    "pre",
+    "figure",
+    "figcaption",
+    "dl",
+    "dt",
+    "dd",
+    "address",
+    "details",
+    "summary",
+    "br",
+    "hr",
+];
+
+/// Extracts the readable text of a saved web page, along with its title and address.
+pub fn extract(path: &str) -> Result<ExtractedDocument> {
+    let bytes = std::fs::read(path).context("Failed to read content from file.")?;
+    let source = String::from_utf8_lossy(&bytes);
+    let document = Html::parse_document(&source);
+
+    Ok(ExtractedDocument {
+        content: readable_text(&document),
+        metadata: AttachmentMetadata {
+            title: title(&document),
+            url: canonical_url(&document, &source),
+        },
+        parts: Vec::new(),
+        group_parts: false,
+    })
+}
+
+/// The main content of a page as Markdown-like text.
+pub fn readable_text(document: &Html) -> String {
+    // Pages that mark their content are trusted, the rest is filtered with heuristics.
+    let root = ["main", "article", "[role=main]", "body"]
+        .iter()
+        .find_map(|s| document.select(&selector(s)).next())
+        .unwrap_or_else(|| document.root_element());
+
+    let mut walker = Walker::default();
+    walker.walk(root);
+    walker.flush();
+
+    walker.builder.finish()
+}
+
+#[derive(Default)]
+struct Walker {
+    builder: TextBuilder,
+    // Text of the paragraph being read, flushed at the next block boundary.
+    inline: String,
+}
+
+impl Walker {
+    fn walk(&mut self, element: ElementRef) {
+        for child in element.children() {
+            match child.value() {
+                Node::Text(text) => self.inline.push_str(text),
+                Node::Element(_) => {
+                    if let Some(child) = ElementRef::wrap(child) {
+                        self.element(child);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn element(&mut self, element: ElementRef) {
+        let name = element.value().name();
+        if is_boilerplate(element) {
+            return;
+        }
+
+        match name {
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                self.flush();
+                let level = name[1..].parse().unwrap_or(1);
+                self.builder.heading(level, &text_of(element));
+            }
+            "li" => {
+                self.flush();
+                self.builder
+                    .list_item(&collapse_whitespace(&text_of(element)));
+            }
+            "table" => {
+                self.flush();
+                self.builder.table(&table_rows(element));
+            }
+            "pre" => {
+                self.flush();
+                self.builder.paragraph(&text_of(element));
+            }
+            _ if BLOCK_TAGS.contains(&name) => {
+                self.flush();
+                self.walk(element);
+                self.flush();
+            }
+            _ => self.walk(element),
+        }
+    }
+
+    fn flush(&mut self) {
+        self.builder.paragraph(&collapse_whitespace(&self.inline));
+        self.inline.clear();
+    }
+}
+
+fn is_boilerplate(element: ElementRef) -> bool {
+    let value = element.value();
+
+    if SKIPPED_TAGS.contains(&value.name())
+        || value.attr("hidden").is_some()
+        || value.attr("aria-hidden") == Some("true")
+    {
+        return true;
+    }
+
+    if matches!(
+        value.attr("role"),
+        Some("navigation" | "banner" | "contentinfo" | "complementary" | "search")
+    ) {
+        return true;
+    }
+
+    // Names are split into words, so `main-nav` matches but `canvas` doesn't.
+    let names = value.classes().chain(value.id());
+    names
+        .flat_map(|n| n.split(['-', '_']))
+        .any(|word| BOILERPLATE_HINTS.contains(&word.to_lowercase().as_str()))
+}
+
+/// Text of an element without the boilerplate inside it.
+fn text_of(element: ElementRef) -> String {
+    let mut text = String::new();
+
+    for child in element.children() {
+        match child.value() {
+            Node::Text(t) => text.push_str(t),
+            Node::Element(_) => {
+                if let Some(child) = ElementRef::wrap(child) {
+                    if !is_boilerplate(child) {
+                        text.push_str(&text_of(child));
+                        if child.value().name() == "br" {
+                            text.push('\n');
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    text
+}
+
+fn table_rows(table: ElementRef) -> Vec<Vec<String>> {
+    let cells = selector("th, td");
+
+    table
+        .select(&selector("tr"))
+        .map(|row| row.select(&cells).map(text_of).collect())
+        .collect()
+}
+
+fn title(document: &Html) -> Option<String> {
+    let title = document
+        .select(&selector("title"))
+        .next()
+        .map(|t| collapse_whitespace(&t.text().collect::<String>()))
+        .filter(|t| !t.is_empty());
+
+    title.or_else(|| meta_content(document, "meta[property='og:title']"))
+}
+
+/// The canonical link of the page, then its Open Graph address, then the
+/// `saved from url=` comment browsers add when saving a page.
+fn canonical_url(document: &Html, source: &str) -> Option<String> {
+    let canonical = document
+        .select(&selector("link[rel=canonical]"))
+        .next()
+        .and_then(|l| l.value().attr("href"))
+        .map(|href| href.trim().to_string())
+        .filter(|href| !href.is_empty());
+
+    canonical
+        .or_else(|| meta_content(document, "meta[property='og:url']"))
+        .or_else(|| saved_from_url(source))
+}
+
+fn meta_content(document: &Html, query: &str) -> Option<String> {
+    document
+        .select(&selector(query))
+        .next()
+        .and_then(|m| m.value().attr("content"))
+        .map(|c| c.trim().to_string())
+        .filter(|c| !c.is_empty())
+}
+
+// Looks like `<!-- saved from url=(0029)https://example.com/page -->`.
+fn saved_from_url(source: &str) -> Option<String> {
+    let start = source.find("saved from url=(")?;
+    let rest = &source[start..];
+    let rest = &rest[rest.find(')')? + 1..];
+    let end = rest.find(char::is_whitespace)?;
+
+    Some(rest[..end].to_string()).filter(|u| !u.is_empty())
+}
+
+fn selector(query: &str) -> Selector {
+    Selector::parse(query).expect("Invalid built-in selector")
+}
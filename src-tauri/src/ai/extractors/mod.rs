@@ -0,0 +1,234 @@
+use std::{fs::File, io::Read, path::Path};
+
+use anyhow::{Context, Result};
+use quick_xml::events::BytesStart;
+use zip::ZipArchive;
+
+use crate::{
+    ai::chunker::{ChunkLocation, Part},
+    db::attachments::AttachmentMetadata,
+};
+
+pub mod code;
+pub mod docx;
+pub mod epub;
+pub mod html;
+pub mod odt;
+pub mod tables;
+
+/// File extensions `extract_content` knows how to read, besides source code.
+pub const SUPPORTED_EXTENSIONS: &[&str] = &[
+    "pdf", "md", "txt", "docx", "odt", "html", "htm", "epub", "csv", "tsv", "xlsx", "xls", "ods",
+];
+
+/// Every extension that can be imported, source code included.
+pub fn supported_extensions() -> Vec<&'static str> {
+    SUPPORTED_EXTENSIONS
+        .iter()
+        .copied()
+        .chain(code::extensions())
+        .collect()
+}
+
+pub struct ExtractedDocument {
+    pub content: String,
+    pub metadata: AttachmentMetadata,
+    /// Parts of `content` with their own location, like the chapters of a book.
+    /// Empty when the whole document is one piece.
+    pub parts: Vec<Part>,
+    /// Parts are small records, like table rows, that are packed together into
+    /// chunks instead of being split one by one.
+    pub group_parts: bool,
+}
+
+impl From<String> for ExtractedDocument {
+    fn from(content: String) -> Self {
+        Self {
+            content,
+            metadata: AttachmentMetadata::default(),
+            parts: Vec::new(),
+            group_parts: false,
+        }
+    }
+}
+
+pub fn is_supported(path: &Path) -> bool {
+    path.extension()
+        .and_then(|s| s.to_str())
+        .is_some_and(|e| supported_extensions().contains(&e.to_lowercase().as_str()))
+}
+
+/// Reads the text of a supported file. PDF parsing is slow, so this should run
+/// outside of the async runtime.
+pub fn extract_content(file_path: &str) -> Result<ExtractedDocument> {
+    let extension = Path::new(file_path)
+        .extension()
+        .and_then(|s| s.to_str())
+        .unwrap_or("");
+
+    let extension = extension.to_lowercase();
+    let document = match extension.as_str() {
+        "pdf" => extract_content_from_pdf(file_path)?,
+        "md" | "txt" => extract_content_from_plain_text(file_path)?.into(),
+        "docx" => docx::extract(file_path)?.into(),
+        "odt" => odt::extract(file_path)?.into(),
+        "html" | "htm" => html::extract(file_path)?,
+        "epub" => epub::extract(file_path)?,
+        "csv" => tables::extract_delimited(file_path, b',')?,
+        "tsv" => tables::extract_delimited(file_path, b'\t')?,
+        "xlsx" | "xls" | "ods" => tables::extract_workbook(file_path)?,
+        ext if code::extensions().any(|e| e == ext) => code::extract(file_path, ext)?,
+        _ => anyhow::bail!("Unsupported file format: .{}", extension),
+    };
+
+    Ok(document)
+}
+
+/// Reads a PDF page by page, each page being a part of the document so chunks
+/// know which page they come from.
+fn extract_content_from_pdf<P: AsRef<Path>>(path: P) -> Result<ExtractedDocument> {
+    let pages =
+        pdf_extract::extract_text_by_pages(path).context("Failed to extract text from PDF file")?;
+
+    let mut content = String::new();
+    let mut parts = Vec::new();
+
+    for (i, page) in pages.iter().enumerate() {
+        let text = page.trim();
+        if text.is_empty() {
+            continue;
+        }
+
+        if !content.is_empty() {
+            content.push_str("\n\n");
+        }
+
+        let start = content.len();
+        content.push_str(text);
+
+        parts.push(Part {
+            range: start..content.len(),
+            location: ChunkLocation {
+                page: Some(i + 1),
+                ..Default::default()
+            },
+        });
+    }
+
+    Ok(ExtractedDocument {
+        content,
+        metadata: AttachmentMetadata::default(),
+        parts,
+        group_parts: false,
+    })
+}
+
+fn extract_content_from_plain_text<P: AsRef<Path>>(path: P) -> Result<String> {
+    let content = std::fs::read_to_string(&path).context("Failed to read content from file.")?;
+
+    Ok(content)
+}
+
+/// Writes the blocks of a structured document as Markdown-like text, so the
+/// chunker still sees where the headings are.
+#[derive(Default)]
+pub struct TextBuilder {
+    out: String,
+}
+
+impl TextBuilder {
+    pub fn heading(&mut self, level: usize, text: &str) {
+        let text = collapse_whitespace(text);
+        if !text.is_empty() {
+            self.push_block(&format!("{} {}", "#".repeat(level.clamp(1, 6)), text));
+        }
+    }
+
+    pub fn paragraph(&mut self, text: &str) {
+        let text = text.trim();
+        if !text.is_empty() {
+            self.push_block(text);
+        }
+    }
+
+    pub fn list_item(&mut self, text: &str) {
+        let text = text.trim();
+        if !text.is_empty() {
+            // Items of the same list stay in one paragraph.
+            if self.out.ends_with("\n\n") && self.last_block().starts_with("- ") {
+                self.out.pop();
+            }
+            self.push_block(&format!("- {}", text));
+        }
+    }
+
+    /// Tables are flattened to one line per row, with cells separated by `|`.
+    pub fn table(&mut self, rows: &[Vec<String>]) {
+        let lines: Vec<String> = rows
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|cell| collapse_whitespace(cell))
+                    .collect::<Vec<_>>()
+                    .join(" | ")
+            })
+            .filter(|line| !line.replace('|', "").trim().is_empty())
+            .collect();
+
+        if !lines.is_empty() {
+            self.push_block(&lines.join("\n"));
+        }
+    }
+
+    pub fn finish(self) -> String {
+        self.out.trim_end().to_string()
+    }
+
+    fn push_block(&mut self, block: &str) {
+        self.out.push_str(block);
+        self.out.push_str("\n\n");
+    }
+
+    fn last_block(&self) -> &str {
+        let trimmed = self.out.trim_end();
+        let start = trimmed.rfind('\n').map(|i| i + 1).unwrap_or(0);
+        &trimmed[start..]
+    }
+}
+
+pub fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Value of an XML attribute, unescaped.
+pub fn attribute(element: &BytesStart, name: &str) -> Option<String> {
+    element
+        .try_get_attribute(name)
+        .ok()
+        .flatten()
+        .and_then(|a| a.unescape_value().ok())
+        .map(|v| v.to_string())
+}
+
+/// Reads a file of a zip based document. Returns `None` when the archive doesn't have it.
+pub fn read_zip_entry(archive: &mut ZipArchive<File>, name: &str) -> Result<Option<String>> {
+    let mut entry = match archive.by_name(name) {
+        Ok(entry) => entry,
+        Err(zip::result::ZipError::FileNotFound) => return Ok(None),
+        Err(e) => {
+            return Err(e).with_context(|| format!("Failed to read {} from the archive", name))
+        }
+    };
+
+    let mut content = String::new();
+    entry
+        .read_to_string(&mut content)
+        .with_context(|| format!("Failed to read {} from the archive", name))?;
+
+    Ok(Some(content))
+}
+
+pub fn open_zip(path: &str) -> Result<ZipArchive<File>> {
+    let file = File::open(path).context("Failed to open the file.")?;
+    ZipArchive::new(file).context("The file is not a valid document archive.")
+}
@@ -0,0 +1,157 @@
+use anyhow::{Context, Result};
+use quick_xml::{events::Event, Reader};
+
+use super::{attribute, open_zip, read_zip_entry, TextBuilder};
+
+#[derive(Default)]
+struct Paragraph {
+    text: String,
+    heading: Option<usize>,
+}
+
+/// Extracts the text of an OpenDocument text file, with its notes at the end.
+pub fn extract(path: &str) -> Result<String> {
+    let mut archive = open_zip(path)?;
+
+    let content = read_zip_entry(&mut archive, "content.xml")?
+        .context("This OpenDocument file has no content.")?;
+
+    let mut reader = Reader::from_str(&content);
+    let mut builder = TextBuilder::default();
+    let mut notes = TextBuilder::default();
+
+    let mut paragraph = Paragraph::default();
+    // Open paragraphs and headings. Text outside of them, like styles, is ignored.
+    let mut paragraph_depth = 0;
+    let mut list_depth = 0;
+
+    // Notes are written inside the paragraph that references them, so that
+    // paragraph is put aside while the note body is read.
+    let mut outer_paragraphs: Vec<Paragraph> = Vec::new();
+    let mut in_citation = false;
+    let mut citation: Option<String> = None;
+
+    // Nested tables are flattened into the cells of the outermost one.
+    let mut table_depth = 0;
+    let mut rows: Vec<Vec<String>> = Vec::new();
+    let mut cell: Option<String> = None;
+
+    loop {
+        match reader
+            .read_event()
+            .context("Failed to parse the OpenDocument file.")?
+        {
+            Event::Start(e) => match e.name().as_ref() {
+                b"text:p" => {
+                    paragraph_depth += 1;
+                    paragraph = Paragraph::default();
+                }
+                b"text:h" => {
+                    paragraph_depth += 1;
+                    paragraph = Paragraph {
+                        text: String::new(),
+                        heading: Some(
+                            attribute(&e, "text:outline-level")
+                                .and_then(|l| l.parse().ok())
+                                .unwrap_or(1),
+                        ),
+                    };
+                }
+                b"text:list" => list_depth += 1,
+                b"text:note-citation" => in_citation = true,
+                b"text:note-body" => outer_paragraphs.push(std::mem::take(&mut paragraph)),
+                b"table:table" => {
+                    table_depth += 1;
+                    if table_depth == 1 {
+                        rows.clear();
+                    }
+                }
+                b"table:table-row" if table_depth == 1 => rows.push(Vec::new()),
+                b"table:table-cell" if table_depth == 1 => cell = Some(String::new()),
+                _ => {}
+            },
+            Event::Empty(e) => match e.name().as_ref() {
+                b"text:s" => {
+                    let count = attribute(&e, "text:c")
+                        .and_then(|c| c.parse().ok())
+                        .unwrap_or(1);
+                    paragraph.text.push_str(&" ".repeat(count));
+                }
+                b"text:tab" => paragraph.text.push('\t'),
+                b"text:line-break" => paragraph.text.push('\n'),
+                b"table:table-cell" if table_depth == 1 => {
+                    if let Some(row) = rows.last_mut() {
+                        row.push(String::new());
+                    }
+                }
+                _ => {}
+            },
+            Event::Text(t) if paragraph_depth > 0 => {
+                let text = t
+                    .unescape()
+                    .context("Invalid text in the OpenDocument file.")?;
+
+                if in_citation {
+                    paragraph.text.push_str(&format!("[^{}]", text.trim()));
+                    citation = Some(text.trim().to_string());
+                } else {
+                    paragraph.text.push_str(&text);
+                }
+            }
+            Event::End(e) => match e.name().as_ref() {
+                b"text:p" | b"text:h" => {
+                    paragraph_depth -= 1;
+
+                    let text = paragraph.text.trim().to_string();
+                    if text.is_empty() {
+                        continue;
+                    }
+
+                    if !outer_paragraphs.is_empty() {
+                        match citation.take() {
+                            Some(c) => notes.paragraph(&format!("[^{}]: {}", c, text)),
+                            None => notes.paragraph(&text),
+                        }
+                    } else if let Some(cell) = cell.as_mut() {
+                        cell.push(' ');
+                        cell.push_str(&text);
+                    } else if let Some(level) = paragraph.heading {
+                        builder.heading(level, &text);
+                    } else if list_depth > 0 {
+                        builder.list_item(&text);
+                    } else {
+                        builder.paragraph(&text);
+                    }
+                }
+                b"text:list" => list_depth -= 1,
+                b"text:note-citation" => in_citation = false,
+                b"text:note-body" => {
+                    paragraph = outer_paragraphs.pop().unwrap_or_default();
+                    citation = None;
+                }
+                b"table:table-cell" if table_depth == 1 => {
+                    if let (Some(text), Some(row)) = (cell.take(), rows.last_mut()) {
+                        row.push(text.trim().to_string());
+                    }
+                }
+                b"table:table" => {
+                    table_depth -= 1;
+                    if table_depth == 0 {
+                        builder.table(&rows);
+                    }
+                }
+                _ => {}
+            },
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    let notes = notes.finish();
+    if !notes.is_empty() {
+        builder.heading(1, "Notes");
+        builder.paragraph(&notes);
+    }
+
+    Ok(builder.finish())
+}
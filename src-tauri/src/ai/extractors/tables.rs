@@ -0,0 +1,122 @@
+use anyhow::{Context, Result};
+use calamine::{open_workbook_auto, Reader};
+
+use super::{collapse_whitespace, ExtractedDocument};
+use crate::{
+    ai::chunker::{ChunkLocation, Part},
+    db::attachments::AttachmentMetadata,
+};
+
+/// Turns every row of a CSV or TSV file into a record labelled with the column headers.
+pub fn extract_delimited(path: &str, delimiter: u8) -> Result<ExtractedDocument> {
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .has_headers(false)
+        .flexible(true)
+        .from_path(path)
+        .context("Failed to open the table file.")?;
+
+    let mut rows = Vec::new();
+    for (i, record) in reader.records().enumerate() {
+        let record = record.context("Failed to read a row of the table file.")?;
+        rows.push((i + 1, record.iter().map(str::to_string).collect()));
+    }
+
+    let mut table = TableWriter::default();
+    table.push_sheet(None, rows);
+
+    Ok(table.finish())
+}
+
+/// Same as `extract_delimited` for every sheet of a workbook.
+pub fn extract_workbook(path: &str) -> Result<ExtractedDocument> {
+    let mut workbook = open_workbook_auto(path).context("Failed to open the spreadsheet.")?;
+    let mut table = TableWriter::default();
+
+    for name in workbook.sheet_names() {
+        let range = workbook
+            .worksheet_range(&name)
+            .with_context(|| format!("Failed to read the sheet {}", name))?;
+
+        // Sheets don't always start at the first row, numbers should match what
+        // the spreadsheet app shows.
+        let first_row = range.start().map(|(row, _)| row as usize).unwrap_or(0);
+
+        let rows = range
+            .rows()
+            .enumerate()
+            .map(|(i, row)| {
+                (
+                    first_row + i + 1,
+                    row.iter().map(|c| c.to_string()).collect(),
+                )
+            })
+            .collect();
+
+        table.push_sheet(Some(&name), rows);
+    }
+
+    Ok(table.finish())
+}
+
+#[derive(Default)]
+struct TableWriter {
+    content: String,
+    parts: Vec<Part>,
+}
+
+impl TableWriter {
+    /// Writes one record per row, the first non-empty row being the headers.
+    fn push_sheet(&mut self, sheet: Option<&str>, rows: Vec<(usize, Vec<String>)>) {
+        let mut rows = rows.into_iter().filter_map(|(number, cells)| {
+            let cells: Vec<String> = cells.iter().map(|c| collapse_whitespace(c)).collect();
+            cells
+                .iter()
+                .any(|c| !c.is_empty())
+                .then_some((number, cells))
+        });
+
+        let Some((_, headers)) = rows.next() else {
+            return;
+        };
+
+        for (number, cells) in rows {
+            let fields: Vec<String> = cells
+                .iter()
+                .enumerate()
+                .filter(|(_, value)| !value.is_empty())
+                .map(
+                    |(i, value)| match headers.get(i).filter(|h| !h.is_empty()) {
+                        Some(header) => format!("{}: {}", header, value),
+                        None => format!("Column {}: {}", i + 1, value),
+                    },
+                )
+                .collect();
+
+            if !self.content.is_empty() {
+                self.content.push('\n');
+            }
+
+            let start = self.content.len();
+            self.content.push_str(&fields.join(" | "));
+
+            self.parts.push(Part {
+                range: start..self.content.len(),
+                location: ChunkLocation {
+                    section: sheet.map(str::to_string),
+                    rows: Some((number, number)),
+                    ..Default::default()
+                },
+            });
+        }
+    }
+
+    fn finish(self) -> ExtractedDocument {
+        ExtractedDocument {
+            content: self.content,
+            metadata: AttachmentMetadata::default(),
+            parts: self.parts,
+            group_parts: true,
+        }
+    }
+}
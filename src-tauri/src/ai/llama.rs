@@ -1,52 +1,118 @@
-use anyhow::{Context, Result};
-use ollama_rs::{coordinator::Coordinator, generation::chat::ChatMessage, Ollama};
+use std::{collections::HashMap, path::Path};
 
-use crate::db::embeddings::VectorSearchResult;
+use anyhow::Result;
+use futures::StreamExt;
+use ollama_rs::generation::chat::ChatMessage;
+
+use crate::ai::providers::{ChatProvider, ProviderConfig};
+use crate::db::{
+    chat::{ChatEntry, Citation},
+    embeddings::VectorSearchResult,
+};
+
+/// How many past messages of a notebook conversation are sent along with a new question.
+const MAX_HISTORY_MESSAGES: usize = 20;
+
+pub struct ChatAnswer {
+    pub content: String,
+    pub citations: Vec<Citation>,
+}
 
 pub struct Model {
-    coordinator: Coordinator<Vec<ChatMessage>>,
+    provider: Box<dyn ChatProvider>,
+    // One conversation per notebook, keyed by notebook id.
+    sessions: HashMap<String, Vec<ChatMessage>>,
 }
 
 impl Model {
-    pub fn new(model_name: &str) -> Self {
-        let ollama = Ollama::default();
-        let history = Vec::new();
+    pub fn new(provider_config: &ProviderConfig) -> Result<Self> {
+        Ok(Self {
+            provider: provider_config.build()?,
+            sessions: HashMap::new(),
+        })
+    }
 
-        let coordinator = Coordinator::new(ollama, model_name.to_string(), history);
+    /// Swaps the LLM backend. Conversations are kept, so the new provider picks up
+    /// where the old one left off.
+    pub fn set_provider(&mut self, provider: Box<dyn ChatProvider>) {
+        self.provider = provider;
+    }
 
-        return Self {
-            coordinator: coordinator,
-        };
+    pub fn has_session(&self, notebook_id: &str) -> bool {
+        self.sessions.contains_key(notebook_id)
+    }
+
+    /// Starts the conversation of a notebook from its stored chat history.
+    pub fn open_session(&mut self, notebook_id: &str, entries: Vec<ChatEntry>) {
+        let mut history: Vec<ChatMessage> = entries
+            .into_iter()
+            .filter_map(|entry| match entry.role.as_str() {
+                "user" => Some(ChatMessage::user(entry.message)),
+                "assistant" => Some(ChatMessage::assistant(entry.message)),
+                _ => None,
+            })
+            .collect();
+
+        Self::trim_history(&mut history);
+        self.sessions.insert(notebook_id.to_string(), history);
+    }
+
+    pub fn close_session(&mut self, notebook_id: &str) {
+        self.sessions.remove(notebook_id);
     }
 
     pub async fn chat(
         &mut self,
+        notebook_id: &str,
         user_message: &str,
         context_chunks: Vec<VectorSearchResult>,
-    ) -> Result<String> {
-        let mut prompt = format!("Question: {}\n\nContext:\n\n", user_message);
+    ) -> Result<ChatAnswer> {
+        let messages = self.build_messages(notebook_id, user_message, &context_chunks);
 
-        for chunk in context_chunks {
-            let c = format!(
-                "----\nFILE_PATH: {}\nCONTENT: {}\n\n",
-                chunk.file_path, chunk.text
-            );
+        let answer = self.provider.chat(messages).await?;
+        self.remember(notebook_id, user_message, &answer);
 
-            prompt.push_str(&c);
+        Ok(ChatAnswer {
+            citations: Self::cited_chunks(&answer, context_chunks),
+            content: answer,
+        })
+    }
+
+    /// Same as `chat`, but calls `on_token` for every piece of the answer as soon
+    /// as the provider sends it. Returns the full answer once the stream is over.
+    pub async fn chat_stream<F>(
+        &mut self,
+        notebook_id: &str,
+        user_message: &str,
+        context_chunks: Vec<VectorSearchResult>,
+        mut on_token: F,
+    ) -> Result<ChatAnswer>
+    where
+        F: FnMut(&str),
+    {
+        let messages = self.build_messages(notebook_id, user_message, &context_chunks);
+        let mut stream = self.provider.chat_stream(messages).await?;
+
+        let mut answer = String::new();
+        while let Some(token) = stream.next().await {
+            let token = token?;
+
+            if !token.is_empty() {
+                on_token(&token);
+                answer.push_str(&token);
+            }
         }
 
-        let message = ChatMessage::user(prompt);
-        let response = self
-            .coordinator
-            .chat(vec![message])
-            .await
-            .context("Could not register this new message.")?;
+        self.remember(notebook_id, user_message, &answer);
 
-        Ok(response.message.content)
+        Ok(ChatAnswer {
+            citations: Self::cited_chunks(&answer, context_chunks),
+            content: answer,
+        })
     }
 
-    pub async fn inject_system_prompt(&mut self) -> Result<()> {
-        let system = ChatMessage::system(
+    fn system_prompt() -> ChatMessage {
+        ChatMessage::system(
             "You are a helpful assistant.
 
                     IMPORTANT RULES:
@@ -55,14 +121,131 @@ impl Model {
                        'I don't have enough information in the uploaded files to answer that.'
                     3. Do NOT make up information.
                     4. You MUST answer in the SAME language as the user's question.
-                    5. The language rule has priority over all stylistic preferences."
+                    5. The language rule has priority over all stylistic preferences.
+                    6. Every context entry starts with a number like [1]. Cite the entries you
+                       used by writing their number in square brackets, e.g. [1] or [2]."
                 .to_string(),
-        );
+        )
+    }
+
+    /// Builds the messages for a new question: the system prompt, the notebook's past
+    /// conversation and the question itself with the retrieved context.
+    fn build_messages(
+        &self,
+        notebook_id: &str,
+        user_message: &str,
+        context_chunks: &[VectorSearchResult],
+    ) -> Vec<ChatMessage> {
+        let mut messages = vec![Self::system_prompt()];
+
+        if let Some(history) = self.sessions.get(notebook_id) {
+            messages.extend(history.iter().cloned());
+        }
+
+        messages.push(ChatMessage::user(Self::build_prompt(
+            user_message,
+            context_chunks,
+        )));
+
+        messages
+    }
+
+    /// Stores a finished exchange in the notebook's conversation. The plain question is
+    /// kept instead of the full prompt, so old context chunks don't pile up in later requests.
+    fn remember(&mut self, notebook_id: &str, user_message: &str, answer: &str) {
+        let history = self.sessions.entry(notebook_id.to_string()).or_default();
+
+        history.push(ChatMessage::user(user_message.to_string()));
+        history.push(ChatMessage::assistant(answer.to_string()));
+
+        Self::trim_history(history);
+    }
+
+    fn trim_history(history: &mut Vec<ChatMessage>) {
+        if history.len() > MAX_HISTORY_MESSAGES {
+            let excess = history.len() - MAX_HISTORY_MESSAGES;
+            history.drain(..excess);
+        }
+    }
+
+    fn build_prompt(user_message: &str, context_chunks: &[VectorSearchResult]) -> String {
+        let mut prompt = format!("Question: {}\n\nContext:\n\n", user_message);
+
+        for (i, chunk) in context_chunks.iter().enumerate() {
+            let location = chunk
+                .location()
+                .map(|l| format!("LOCATION: {}\n", l))
+                .unwrap_or_default();
+
+            let c = format!(
+                "----\n[{}] FILE_PATH: {}\n{}CONTENT: {}\n\n",
+                i + 1,
+                chunk.file_path,
+                location,
+                chunk.text
+            );
+
+            prompt.push_str(&c);
+        }
+
+        prompt
+    }
+
+    /// Turns the chunks whose `[n]` marker shows up in the answer into citations.
+    fn cited_chunks(answer: &str, context_chunks: Vec<VectorSearchResult>) -> Vec<Citation> {
+        let markers = Self::parse_markers(answer);
+
+        context_chunks
+            .into_iter()
+            .enumerate()
+            .map(|(i, chunk)| (i + 1, chunk))
+            .filter(|(marker, _)| markers.contains(marker))
+            .map(|(marker, chunk)| {
+                let file_name = Path::new(&chunk.file_path)
+                    .file_name()
+                    .map(|n| n.to_string_lossy().to_string())
+                    .unwrap_or_else(|| chunk.file_path.clone());
+
+                Citation {
+                    marker,
+                    attachment_id: chunk.attachment_id,
+                    file_name,
+                    text: chunk.text,
+                    score: chunk.score,
+                    page: chunk.page,
+                    section: chunk.section,
+                    row_start: chunk.row_start,
+                    row_end: chunk.row_end,
+                    line_start: chunk.line_start,
+                    line_end: chunk.line_end,
+                }
+            })
+            .collect()
+    }
+
+    /// Finds every number written between square brackets, `[1]` and `[1, 3]` included.
+    fn parse_markers(answer: &str) -> Vec<usize> {
+        let mut markers = Vec::new();
+        let mut rest = answer;
+
+        while let Some(start) = rest.find('[') {
+            rest = &rest[start + 1..];
+
+            let Some(end) = rest.find(']') else {
+                break;
+            };
+
+            let inner = &rest[..end];
+            let numbers: Option<Vec<usize>> = inner
+                .split(',')
+                .map(|n| n.trim().parse::<usize>().ok())
+                .collect();
+
+            if let Some(numbers) = numbers {
+                markers.extend(numbers);
+            }
+        }
 
-        self.coordinator
-            .chat(vec![system])
-            .await
-            .context("Could not inject the system prompt.")?;
-        Ok(())
+        markers
     }
 }
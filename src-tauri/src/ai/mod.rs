@@ -1,2 +1,6 @@
+pub mod chunker;
 pub mod embeds;
+pub mod extractors;
 pub mod llama;
+pub mod providers;
+pub mod rerank;
@@ -0,0 +1,96 @@
+use anyhow::{Context, Result};
+use futures::{future::BoxFuture, stream::BoxStream};
+use ollama_rs::generation::chat::ChatMessage;
+use serde::{Deserialize, Serialize};
+
+use crate::ai::providers::{ollama::OllamaProvider, openai::OpenAiProvider};
+
+pub mod ollama;
+pub mod openai;
+
+/// Stream of answer pieces, in the order the backend produces them.
+pub type TokenStream = BoxStream<'static, Result<String>>;
+
+/// A backend able to answer a conversation. `Model` only talks to the LLM through this,
+/// so switching between Ollama and an OpenAI-compatible server is just a matter of
+/// handing it another provider.
+pub trait ChatProvider: Send + Sync {
+    fn chat(&self, messages: Vec<ChatMessage>) -> BoxFuture<'_, Result<String>>;
+
+    fn chat_stream(&self, messages: Vec<ChatMessage>) -> BoxFuture<'_, Result<TokenStream>>;
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ProviderConfig {
+    Ollama {
+        host: String,
+        model: String,
+    },
+    OpenAi {
+        base_url: String,
+        model: String,
+        api_key: Option<String>,
+    },
+}
+
+impl Default for ProviderConfig {
+    fn default() -> Self {
+        Self::Ollama {
+            host: "http://127.0.0.1:11434".to_string(),
+            model: "phi4-mini".to_string(),
+        }
+    }
+}
+
+/// Stands in for the API key in settings sent to the frontend.
+const REDACTED_API_KEY: &str = "********";
+
+impl ProviderConfig {
+    /// Copy without the API key, which never leaves the backend.
+    pub fn redacted(&self) -> Self {
+        match self {
+            Self::OpenAi {
+                base_url,
+                model,
+                api_key: Some(_),
+            } => Self::OpenAi {
+                base_url: base_url.clone(),
+                model: model.clone(),
+                api_key: Some(REDACTED_API_KEY.to_string()),
+            },
+            config => config.clone(),
+        }
+    }
+
+    /// Puts back the API key of `current` when the frontend sent the redacted one.
+    pub fn restore_api_key(&mut self, current: &ProviderConfig) {
+        if let (
+            Self::OpenAi { api_key, .. },
+            Self::OpenAi {
+                api_key: current_key,
+                ..
+            },
+        ) = (self, current)
+        {
+            if api_key.as_deref() == Some(REDACTED_API_KEY) {
+                api_key.clone_from(current_key);
+            }
+        }
+    }
+
+    pub fn build(&self) -> Result<Box<dyn ChatProvider>> {
+        let provider: Box<dyn ChatProvider> = match self {
+            Self::Ollama { host, model } => {
+                Box::new(OllamaProvider::new(host, model).context("Invalid Ollama host.")?)
+            }
+            Self::OpenAi {
+                base_url,
+                model,
+                api_key,
+            } => Box::new(OpenAiProvider::new(base_url, model, api_key.clone())),
+        };
+
+        Ok(provider)
+    }
+}
@@ -0,0 +1,63 @@
+use anyhow::{anyhow, Context, Result};
+use futures::{future::BoxFuture, FutureExt, StreamExt};
+use ollama_rs::{
+    generation::chat::{request::ChatMessageRequest, ChatMessage},
+    Ollama,
+};
+
+use crate::ai::providers::{ChatProvider, TokenStream};
+
+pub struct OllamaProvider {
+    ollama: Ollama,
+    model_name: String,
+}
+
+impl OllamaProvider {
+    pub fn new(host: &str, model_name: &str) -> Result<Self> {
+        let ollama = Ollama::try_new(host)?;
+
+        Ok(Self {
+            ollama,
+            model_name: model_name.to_string(),
+        })
+    }
+}
+
+impl ChatProvider for OllamaProvider {
+    fn chat(&self, messages: Vec<ChatMessage>) -> BoxFuture<'_, Result<String>> {
+        async move {
+            let request = ChatMessageRequest::new(self.model_name.clone(), messages);
+
+            let response = self
+                .ollama
+                .send_chat_messages(request)
+                .await
+                .context("Could not register this new message.")?;
+
+            Ok(response.message.content)
+        }
+        .boxed()
+    }
+
+    fn chat_stream(&self, messages: Vec<ChatMessage>) -> BoxFuture<'_, Result<TokenStream>> {
+        async move {
+            let request = ChatMessageRequest::new(self.model_name.clone(), messages);
+
+            let stream = self
+                .ollama
+                .send_chat_messages_stream(request)
+                .await
+                .context("Could not start the chat stream.")?;
+
+            let tokens: TokenStream = stream
+                .map(|item| {
+                    item.map(|response| response.message.content)
+                        .map_err(|_| anyhow!("The chat stream was interrupted."))
+                })
+                .boxed();
+
+            Ok(tokens)
+        }
+        .boxed()
+    }
+}
@@ -0,0 +1,348 @@
+use anyhow::{bail, Context, Result};
+use futures::{future::BoxFuture, stream, FutureExt, StreamExt};
+use ollama_rs::generation::chat::{ChatMessage, MessageRole};
+use serde::{Deserialize, Serialize};
+
+use crate::ai::providers::{ChatProvider, TokenStream};
+
+/// Talks to any server implementing OpenAI's `/chat/completions` endpoint,
+/// such as llama.cpp's server or vLLM.
+pub struct OpenAiProvider {
+    client: reqwest::Client,
+    base_url: String,
+    model_name: String,
+    api_key: Option<String>,
+}
+
+#[derive(Serialize)]
+struct CompletionRequest<'a> {
+    model: &'a str,
+    messages: Vec<CompletionMessage>,
+    stream: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CompletionMessage {
+    role: String,
+    content: String,
+}
+
+#[derive(Deserialize)]
+struct CompletionResponse {
+    choices: Vec<CompletionChoice>,
+}
+
+#[derive(Deserialize)]
+struct CompletionChoice {
+    message: CompletionMessage,
+}
+
+#[derive(Deserialize)]
+struct CompletionChunk {
+    choices: Vec<CompletionChunkChoice>,
+}
+
+#[derive(Deserialize)]
+struct CompletionChunkChoice {
+    delta: CompletionDelta,
+}
+
+#[derive(Deserialize)]
+struct CompletionDelta {
+    content: Option<String>,
+}
+
+impl OpenAiProvider {
+    pub fn new(base_url: &str, model_name: &str, api_key: Option<String>) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            model_name: model_name.to_string(),
+            api_key: api_key.filter(|k| !k.is_empty()),
+        }
+    }
+
+    async fn send(&self, messages: Vec<ChatMessage>, stream: bool) -> Result<reqwest::Response> {
+        let body = CompletionRequest {
+            model: &self.model_name,
+            messages: messages
+                .into_iter()
+                .map(Self::to_completion_message)
+                .collect(),
+            stream,
+        };
+
+        let mut request = self
+            .client
+            .post(format!("{}/chat/completions", self.base_url))
+            .json(&body);
+
+        if let Some(api_key) = &self.api_key {
+            request = request.bearer_auth(api_key);
+        }
+
+        let response = request
+            .send()
+            .await
+            .context("Could not reach the OpenAI-compatible server.")?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let text = response.text().await.unwrap_or_default();
+            bail!("The OpenAI-compatible server answered {}: {}", status, text);
+        }
+
+        Ok(response)
+    }
+
+    fn to_completion_message(message: ChatMessage) -> CompletionMessage {
+        let role = match message.role {
+            MessageRole::User => "user",
+            MessageRole::Assistant => "assistant",
+            MessageRole::System => "system",
+            MessageRole::Tool => "tool",
+        };
+
+        CompletionMessage {
+            role: role.to_string(),
+            content: message.content,
+        }
+    }
+
+    /// Reads one server-sent event line. `Ok(None)` means the line carries no token.
+    fn parse_event_line(line: &str) -> Result<Option<String>> {
+        let Some(data) = line.strip_prefix("data:") else {
+            return Ok(None);
+        };
+
+        let data = data.trim();
+        if data.is_empty() || data == "[DONE]" {
+            return Ok(None);
+        }
+
+        let chunk: CompletionChunk =
+            serde_json::from_str(data).context("Could not parse a chat stream event.")?;
+
+        Ok(chunk
+            .choices
+            .into_iter()
+            .next()
+            .and_then(|choice| choice.delta.content))
+    }
+}
+
+impl ChatProvider for OpenAiProvider {
+    fn chat(&self, messages: Vec<ChatMessage>) -> BoxFuture<'_, Result<String>> {
+        async move {
+            let response: CompletionResponse = self
+                .send(messages, false)
+                .await?
+                .json()
+                .await
+                .context("Could not parse the chat completion.")?;
+
+            let choice = response
+                .choices
+                .into_iter()
+                .next()
+                .context("The chat completion has no choices.")?;
+
+            Ok(choice.message.content)
+        }
+        .boxed()
+    }
+
+    fn chat_stream(&self, messages: Vec<ChatMessage>) -> BoxFuture<'_, Result<TokenStream>> {
+        async move {
+            let bytes = self
+                .send(messages, true)
+                .await?
+                .bytes_stream()
+                .fuse()
+                .boxed();
+
+            // Events are separated by newlines, but a network chunk can end in the
+            // middle of one, so bytes are buffered until a full line is available.
+            // The last line may come without a newline, it's completed once the body ends.
+            let tokens = stream::try_unfold(
+                (bytes, Vec::<u8>::new()),
+                |(mut bytes, mut buffer)| async move {
+                    loop {
+                        if let Some(pos) = buffer.iter().position(|b| *b == b'\n') {
+                            let line: Vec<u8> = buffer.drain(..=pos).collect();
+                            let line = String::from_utf8_lossy(&line);
+
+                            if line.trim() == "data: [DONE]" {
+                                return Ok::<_, anyhow::Error>(None);
+                            }
+
+                            if let Some(token) = Self::parse_event_line(line.trim())? {
+                                return Ok(Some((token, (bytes, buffer))));
+                            }
+
+                            continue;
+                        }
+
+                        match bytes.next().await {
+                            Some(chunk) => buffer.extend_from_slice(
+                                &chunk.context("The chat stream was interrupted.")?,
+                            ),
+                            None if buffer.is_empty() => return Ok(None),
+                            None => buffer.push(b'\n'),
+                        }
+                    }
+                },
+            );
+
+            let tokens: TokenStream = tokens.boxed();
+            Ok(tokens)
+        }
+        .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use futures::TryStreamExt;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::{TcpListener, TcpStream},
+    };
+
+    use super::*;
+
+    const STREAM_HEAD: &str =
+        "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nConnection: close\r\n\r\n";
+
+    /// Answers a single request with `head`, then writes `body` piece by piece so the
+    /// client receives it in several network chunks. Returns the server's base URL.
+    async fn mock_server(head: &'static str, body: Vec<&'static str>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            read_request(&mut socket).await;
+
+            socket.write_all(head.as_bytes()).await.unwrap();
+            for piece in body {
+                socket.write_all(piece.as_bytes()).await.unwrap();
+                socket.flush().await.unwrap();
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+        });
+
+        format!("http://{}", address)
+    }
+
+    /// Reads the headers and the body of a request, so the client isn't cut off
+    /// while it's still sending.
+    async fn read_request(socket: &mut TcpStream) {
+        let mut request = Vec::new();
+        let mut buffer = [0; 4096];
+
+        loop {
+            let read = socket.read(&mut buffer).await.unwrap();
+            if read == 0 {
+                return;
+            }
+            request.extend_from_slice(&buffer[..read]);
+
+            let text = String::from_utf8_lossy(&request);
+            let Some(head_end) = text.find("\r\n\r\n") else {
+                continue;
+            };
+            let content_length = text[..head_end]
+                .lines()
+                .find_map(|line| {
+                    let (name, value) = line.split_once(':')?;
+                    if name.eq_ignore_ascii_case("content-length") {
+                        value.trim().parse::<usize>().ok()
+                    } else {
+                        None
+                    }
+                })
+                .unwrap_or(0);
+
+            if request.len() >= head_end + 4 + content_length {
+                return;
+            }
+        }
+    }
+
+    fn messages() -> Vec<ChatMessage> {
+        vec![ChatMessage::user("Hello?".to_string())]
+    }
+
+    async fn stream_tokens(base_url: &str) -> Result<Vec<String>> {
+        OpenAiProvider::new(base_url, "test", None)
+            .chat_stream(messages())
+            .await?
+            .try_collect()
+            .await
+    }
+
+    #[tokio::test]
+    async fn chat_parses_a_completion() {
+        let base_url = mock_server(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nConnection: close\r\n\r\n",
+            vec![r#"{"choices":[{"message":{"role":"assistant","content":"Hi there"}}]}"#],
+        )
+        .await;
+
+        let answer = OpenAiProvider::new(&base_url, "test", None)
+            .chat(messages())
+            .await
+            .unwrap();
+
+        assert_eq!(answer, "Hi there");
+    }
+
+    #[tokio::test]
+    async fn chat_stream_reads_events_split_across_chunks() {
+        let base_url = mock_server(
+            STREAM_HEAD,
+            vec![
+                r#"data: {"choices":[{"delta":{"content":"Hel"#,
+                "lo\"}}]}\n\ndata: {\"choices\":[{\"delta\":{\"content\":\" world\"}}]}\n",
+                "\ndata: [DONE]\n\n",
+            ],
+        )
+        .await;
+
+        assert_eq!(stream_tokens(&base_url).await.unwrap(), ["Hello", " world"]);
+    }
+
+    #[tokio::test]
+    async fn chat_stream_keeps_a_last_line_without_newline() {
+        let base_url = mock_server(
+            STREAM_HEAD,
+            vec![
+                "data: {\"choices\":[{\"delta\":{\"content\":\"a\"}}]}\n\n",
+                r#"data: {"choices":[{"delta":{"content":"b"}}]}"#,
+            ],
+        )
+        .await;
+
+        assert_eq!(stream_tokens(&base_url).await.unwrap(), ["a", "b"]);
+    }
+
+    const ERROR_HEAD: &str =
+        "HTTP/1.1 500 Internal Server Error\r\nContent-Length: 4\r\nConnection: close\r\n\r\n";
+
+    #[tokio::test]
+    async fn error_status_is_an_error() {
+        let base_url = mock_server(ERROR_HEAD, vec!["oops"]).await;
+        let error = OpenAiProvider::new(&base_url, "test", None)
+            .chat(messages())
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("500"), "{}", error);
+
+        let base_url = mock_server(ERROR_HEAD, vec!["oops"]).await;
+        let error = stream_tokens(&base_url).await.unwrap_err();
+        assert!(error.to_string().contains("500"), "{}", error);
+    }
+}
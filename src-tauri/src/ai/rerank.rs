@@ -0,0 +1,77 @@
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use fastembed::{RerankInitOptions, RerankerModel, TextRerank};
+use serde::{Deserialize, Serialize};
+
+/// Cross-encoder models that can be picked in the settings.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum RerankerModelKind {
+    #[default]
+    BgeRerankerBase,
+    BgeRerankerV2M3,
+    JinaRerankerV1TurboEn,
+    JinaRerankerV2BaseMultilingual,
+}
+
+impl RerankerModelKind {
+    fn to_fastembed(self) -> RerankerModel {
+        match self {
+            Self::BgeRerankerBase => RerankerModel::BGERerankerBase,
+            Self::BgeRerankerV2M3 => RerankerModel::BGERerankerV2M3,
+            Self::JinaRerankerV1TurboEn => RerankerModel::JINARerankerV1TurboEn,
+            Self::JinaRerankerV2BaseMultilingual => RerankerModel::JINARerankerV2BaseMultiligual,
+        }
+    }
+}
+
+/// Scores passages against a query with a cross-encoder, which reads both together
+/// and ranks better than comparing embeddings, but is too slow to run on a whole
+/// notebook. The model is only loaded the first time it's needed.
+pub struct Reranker {
+    model: Option<(RerankerModelKind, TextRerank)>,
+    cache_dir: PathBuf,
+}
+
+impl Reranker {
+    pub fn new(app_data_dir: PathBuf) -> Self {
+        Self {
+            model: None,
+            cache_dir: app_data_dir.join("fastembed_cache"),
+        }
+    }
+
+    /// Relevance of each passage to the query, in the order of `passages`. Higher is better.
+    pub fn score(
+        &mut self,
+        kind: RerankerModelKind,
+        query: &str,
+        passages: &[&str],
+    ) -> Result<Vec<f32>> {
+        let model = self.load(kind)?;
+
+        let results = model
+            .rerank(query, passages, false, None)
+            .context("Failed to rerank the retrieved passages.")?;
+
+        let mut scores = vec![f32::MIN; passages.len()];
+        for result in results {
+            scores[result.index] = result.score;
+        }
+
+        Ok(scores)
+    }
+
+    fn load(&mut self, kind: RerankerModelKind) -> Result<&mut TextRerank> {
+        if !matches!(&self.model, Some((loaded, _)) if *loaded == kind) {
+            let options =
+                RerankInitOptions::new(kind.to_fastembed()).with_cache_dir(self.cache_dir.clone());
+            let model =
+                TextRerank::try_new(options).context("Failed to initialize the reranker model.")?;
+            self.model = Some((kind, model));
+        }
+
+        Ok(&mut self.model.as_mut().expect("The reranker was just loaded").1)
+    }
+}
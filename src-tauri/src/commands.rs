@@ -1,8 +1,14 @@
-use crate::db::attachments::{Attachment, AttachmentStatus};
+use crate::ai::extractors;
+use crate::db::attachments::Attachment;
 use crate::db::chat::ChatEntry;
+use crate::db::embeddings::SearchMode;
 use crate::db::notebooks::Notebook;
+use crate::db::settings::Settings;
+use crate::db::watched_folders::WatchedFolder;
+use crate::import::{self, ImportSummary};
+use crate::retrieval::{self, RetrievalOptions, SearchHit};
 use crate::state::AppState;
-use futures::TryFutureExt;
+use crate::watcher::{self, FolderSync};
 use ollama_rs::generation::chat::MessageRole;
 use serde::Serialize;
 use tauri::async_runtime::Mutex;
@@ -15,6 +21,27 @@ pub struct CommandError {
     pub reason: String,
 }
 
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ChatTokenEvent {
+    pub request_id: String,
+    pub token: String,
+}
+
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ChatDoneEvent {
+    pub request_id: String,
+    pub entry: ChatEntry,
+}
+
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ChatErrorEvent {
+    pub request_id: String,
+    pub reason: String,
+}
+
 // Alias to simplify the command signatures
 type CommandResult<T> = Result<T, CommandError>;
 
@@ -48,12 +75,21 @@ pub async fn get_notebooks(state: State<'_, AppState>) -> CommandResult<Vec<Note
 }
 #[tauri::command]
 pub async fn delete_notebook(state: State<'_, AppState>, notebook_id: String) -> CommandResult<()> {
+    let attachments = state
+        .db
+        .get_attachments_repository()
+        .get_by_notebook(&notebook_id)
+        .await?;
+
     state
         .db
         .get_notebooks_repository()
         .delete(&notebook_id)
-        .await
-        .map_err(Into::into)
+        .await?;
+
+    state.chat_model.lock().await.close_session(&notebook_id);
+    import::release_stored_files(&state, attachments).await;
+    Ok(())
 }
 
 #[tauri::command]
@@ -85,138 +121,155 @@ pub async fn get_attachments(
 }
 
 #[tauri::command]
-pub async fn upload_file(
+pub async fn upload_files(
     app: tauri::AppHandle,
     state: tauri::State<'_, AppState>,
     notebook_id: String,
-) -> CommandResult<Attachment> {
-    // 1. Open File Picker (blocking is fine in this async task)
-    let file_path = app.dialog().file().blocking_pick_file();
-    let mut tx = state
-        .db
-        .begin_transaction()
-        .map_err(|e| CommandError {
-            reason: e.to_string(),
-        })
-        .await?;
+) -> CommandResult<ImportSummary> {
+    // Blocking is fine in this async task.
+    let files = app
+        .dialog()
+        .file()
+        .add_filter("Documents", &extractors::supported_extensions())
+        .blocking_pick_files()
+        .ok_or_else(|| CommandError {
+            reason: "No file selected".to_string(),
+        })?;
 
-    let path_buf = file_path.ok_or_else(|| CommandError {
-        reason: "No file selected".to_string(),
-    })?;
+    let paths = files
+        .into_iter()
+        .filter_map(|f| f.into_path().ok())
+        .collect();
 
-    // 2. Extract Metadata
-    let path = path_buf.as_path().ok_or_else(|| CommandError {
-        reason: "Invalid file path".to_string(),
-    })?;
+    Ok(import::import_files(&app, &state, &notebook_id, paths, None).await?)
+}
 
-    let file_name = path
-        .file_name()
-        .and_then(|n| n.to_str())
+/// Imports every supported file of a folder and its subfolders.
+#[tauri::command]
+pub async fn import_folder(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    notebook_id: String,
+) -> CommandResult<ImportSummary> {
+    let folder = app
+        .dialog()
+        .file()
+        .blocking_pick_folder()
+        .and_then(|f| f.into_path().ok())
         .ok_or_else(|| CommandError {
-            reason: "File not found.".to_string(),
-        })?
-        .to_string();
+            reason: "No folder selected".to_string(),
+        })?;
 
-    println!("FileName: {}", file_name);
+    let paths = import::collect_folder(&folder)?;
 
-    let metadata = std::fs::metadata(path).map_err(|e| CommandError {
-        reason: format!("Failed to read file metadata: {}", e),
-    })?;
+    Ok(import::import_files(&app, &state, &notebook_id, paths, None).await?)
+}
 
-    let size = metadata.len() as i64;
-    let mime = path
-        .extension()
-        .and_then(|s| s.to_str())
-        .unwrap_or("bin")
-        .to_string();
+/// Watches a folder: its files are imported now, and kept in sync afterwards.
+#[tauri::command]
+pub async fn watch_folder(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    notebook_id: String,
+) -> CommandResult<FolderSync> {
+    let folder = app
+        .dialog()
+        .file()
+        .blocking_pick_folder()
+        .and_then(|f| f.into_path().ok())
+        .ok_or_else(|| CommandError {
+            reason: "No folder selected".to_string(),
+        })?;
 
-    // 3. Save to SQLite via Repository
-    let attachment = state
+    let folder = state
         .db
-        .get_attachments_repository()
-        .create_with_tx(
-            &mut tx,
-            notebook_id.clone(),
-            file_name,
-            path.to_string_lossy().to_string(),
-            size,
-            mime,
-        )
+        .get_watched_folders_repository()
+        .create(&notebook_id, &folder.to_string_lossy())
+        .await?;
+
+    Ok(watcher::sync_folder(&app, &state, &folder, true).await?)
+}
+
+#[tauri::command]
+pub async fn get_watched_folders(
+    state: tauri::State<'_, AppState>,
+    notebook_id: String,
+) -> CommandResult<Vec<WatchedFolder>> {
+    state
+        .db
+        .get_watched_folders_repository()
+        .get_by_notebook(&notebook_id)
         .await
-        .map_err(|e| CommandError {
-            reason: e.to_string(),
-        })?;
+        .map_err(Into::into)
+}
 
-    let app_handle = app.clone();
-    let state_owned: AppState = state.inner().clone();
-    let attachment_id = attachment.id.clone();
-    let notebook_id = notebook_id.clone();
-    let path_string = path.to_str().map(|s| s.to_string());
+#[tauri::command]
+pub async fn sync_watched_folder(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    id: String,
+) -> CommandResult<FolderSync> {
+    let folder = state
+        .db
+        .get_watched_folders_repository()
+        .get_by_id(&id)
+        .await?;
 
-    tauri::async_runtime::spawn(async move {
-        app_handle.emit("processing-start", &attachment_id).ok();
-
-        let path_str = match path_string {
-            Some(ref s) => s,
-            None => return,
-        };
-
-        let result: Result<(), anyhow::Error> = async {
-            let batch = {
-                let mut model = state_owned.embeddings_model.lock().await;
-                model
-                    .generate_from_file(path_str, &notebook_id, &attachment_id)
-                    .await?
-                    .batch
-            };
+    Ok(watcher::sync_folder(&app, &state, &folder, true).await?)
+}
 
-            state_owned
-                .db
-                .get_embeddings_repository()
-                .add_document(batch)
-                .await?;
+/// Stops syncing a folder. The files already imported stay in the notebook.
+#[tauri::command]
+pub async fn unwatch_folder(state: tauri::State<'_, AppState>, id: String) -> CommandResult<()> {
+    state
+        .db
+        .get_watched_folders_repository()
+        .delete(&id)
+        .await
+        .map_err(Into::into)
+}
 
-            state_owned
-                .db
-                .get_attachments_repository()
-                .update_status(&attachment_id, AttachmentStatus::Ready)
-                .await?;
+#[tauri::command]
+pub async fn cancel_ingestion(
+    state: tauri::State<'_, AppState>,
+    attachment_id: String,
+) -> CommandResult<()> {
+    if !state.ingestion.cancel(&attachment_id) {
+        return Err(CommandError {
+            reason: "This file is not being processed.".to_string(),
+        });
+    }
 
-            Ok(())
-        }
-        .await;
+    Ok(())
+}
 
-        match result {
-            Ok(_) => {
-                app_handle
-                    .emit("processing-success", &attachment_id)
-                    .unwrap();
-            }
-            Err(e) => {
-                eprintln!("Job failed: {}", e);
-                state_owned
-                    .db
-                    .get_attachments_repository()
-                    .update_status(&attachment_id, AttachmentStatus::Error)
-                    .await
-                    .ok();
-                app_handle
-                    .emit(
-                        "processing-error",
-                        CommandError {
-                            reason: "Something went wron.".to_string(),
-                        },
-                    )
-                    .unwrap();
-            }
-        }
-    });
+/// Indexes an attachment again, from a fresh copy of its original file if it still exists.
+#[tauri::command]
+pub async fn reindex_attachment(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    id: String,
+) -> CommandResult<Attachment> {
+    let attachment = state.db.get_attachments_repository().get_by_id(&id).await?;
+
+    Ok(import::reindex(&app, &state, attachment).await?)
+}
 
-    tx.commit().await.map_err(|e| CommandError {
-        reason: format!("Failed to commit database transaction: {}", e),
-    })?;
+/// Loads the notebook's stored conversation into the chat model the first time it's used.
+async fn ensure_chat_session(state: &AppState, notebook_id: &str) -> anyhow::Result<()> {
+    let mut model = state.chat_model.lock().await;
+    if model.has_session(notebook_id) {
+        return Ok(());
+    }
 
-    Ok(attachment)
+    let history = state
+        .db
+        .get_chat_entry_repository()
+        .get_by_notebook_id(notebook_id)
+        .await?;
+
+    model.open_session(notebook_id, history);
+    Ok(())
 }
 
 #[tauri::command]
@@ -224,17 +277,16 @@ pub async fn chat(
     state: tauri::State<'_, AppState>,
     notebook_id: String,
     message: String,
-) -> CommandResult<String> {
-    let query_message_batch = {
-        let mut model = state.embeddings_model.lock().await;
-        model.generate_from_text(&message).await?
+    search_mode: Option<SearchMode>,
+    attachment_ids: Option<Vec<String>>,
+) -> CommandResult<ChatEntry> {
+    let options = RetrievalOptions {
+        search_mode,
+        attachment_ids,
+        ..Default::default()
     };
-
-    let embedding_response = state
-        .db
-        .get_embeddings_repository()
-        .search(&notebook_id.clone(), query_message_batch, 5)
-        .await?;
+    let embedding_response = retrieval::retrieve(&state, &notebook_id, &message, &options).await?;
+    ensure_chat_session(&state, &notebook_id).await?;
 
     state
         .db
@@ -246,53 +298,181 @@ pub async fn chat(
         .chat_model
         .lock()
         .await
-        .chat(&message, embedding_response)
+        .chat(&notebook_id, &message, embedding_response)
         .await?;
 
     let r = state
         .db
         .get_chat_entry_repository()
-        .create(&notebook_id, MessageRole::Assistant, response)
+        .create_with_citations(
+            &notebook_id,
+            MessageRole::Assistant,
+            response.content,
+            response.citations,
+        )
         .await?;
 
-    Ok(r.message)
+    Ok(r)
 }
 
+/// Streaming version of `chat`. Returns right away and reports the answer through
+/// the `chat-token`, `chat-done` and `chat-error` events, all tagged with `request_id`
+/// so the frontend can tell concurrent requests apart.
 #[tauri::command]
-pub async fn delete_attachment(state: tauri::State<'_, AppState>, id: String) -> CommandResult<()> {
-    let mut tx = state
-        .db
-        .begin_transaction()
-        .map_err(|e| CommandError {
-            reason: e.to_string(),
-        })
-        .await?;
+pub async fn chat_stream(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    notebook_id: String,
+    message: String,
+    request_id: String,
+    search_mode: Option<SearchMode>,
+    attachment_ids: Option<Vec<String>>,
+) -> CommandResult<()> {
+    let options = RetrievalOptions {
+        search_mode,
+        attachment_ids,
+        ..Default::default()
+    };
+    let embedding_response = retrieval::retrieve(&state, &notebook_id, &message, &options).await?;
+    ensure_chat_session(&state, &notebook_id).await?;
 
     state
         .db
-        .get_attachments_repository()
-        .delete_with_tx(&mut tx, &id.clone())
-        .await
-        .map_err(|e| CommandError {
-            reason: e.to_string(),
-        })?;
+        .get_chat_entry_repository()
+        .create(&notebook_id, MessageRole::User, message.clone())
+        .await?;
 
-    state
-        .db
-        .get_embeddings_repository()
-        .remove_document_embeddings(&id)
-        .await
-        .map_err(|e| CommandError {
-            reason: e.to_string(),
-        })?;
+    let state_owned: AppState = state.inner().clone();
+
+    tauri::async_runtime::spawn(async move {
+        let result: Result<ChatEntry, anyhow::Error> = async {
+            let response = state_owned
+                .chat_model
+                .lock()
+                .await
+                .chat_stream(&notebook_id, &message, embedding_response, |token| {
+                    app.emit(
+                        "chat-token",
+                        ChatTokenEvent {
+                            request_id: request_id.clone(),
+                            token: token.to_string(),
+                        },
+                    )
+                    .ok();
+                })
+                .await?;
 
-    tx.commit().await.map_err(|e| CommandError {
-        reason: format!("Failed to commit database transaction: {}", e),
-    })?;
+            state_owned
+                .db
+                .get_chat_entry_repository()
+                .create_with_citations(
+                    &notebook_id,
+                    MessageRole::Assistant,
+                    response.content,
+                    response.citations,
+                )
+                .await
+        }
+        .await;
+
+        match result {
+            Ok(entry) => {
+                app.emit("chat-done", ChatDoneEvent { request_id, entry })
+                    .ok();
+            }
+            Err(e) => {
+                eprintln!("Chat stream failed: {}", e);
+                app.emit(
+                    "chat-error",
+                    ChatErrorEvent {
+                        request_id,
+                        reason: e.to_string(),
+                    },
+                )
+                .ok();
+            }
+        }
+    });
 
     Ok(())
 }
 
+/// Finds passages in a notebook without asking the LLM anything.
+#[tauri::command]
+pub async fn search_notebook(
+    state: tauri::State<'_, AppState>,
+    notebook_id: String,
+    query: String,
+    top_k: Option<usize>,
+    attachment_ids: Option<Vec<String>>,
+    search_mode: Option<SearchMode>,
+) -> CommandResult<Vec<SearchHit>> {
+    if top_k.is_some_and(|k| !(1..=50).contains(&k)) {
+        return Err(CommandError {
+            reason: "Top-k must be between 1 and 50.".to_string(),
+        });
+    }
+
+    let options = RetrievalOptions {
+        top_k,
+        search_mode,
+        attachment_ids,
+    };
+
+    retrieval::search(&state, &notebook_id, &query, &options)
+        .await
+        .map_err(Into::into)
+}
+
+#[tauri::command]
+pub async fn delete_attachment(state: tauri::State<'_, AppState>, id: String) -> CommandResult<()> {
+    let attachment = state.db.get_attachments_repository().get_by_id(&id).await?;
+
+    Ok(import::remove_attachment(&state, attachment).await?)
+}
+
+#[tauri::command]
+pub async fn get_settings(state: tauri::State<'_, AppState>) -> CommandResult<Settings> {
+    Ok(state.settings.lock().await.redacted())
+}
+
+/// Validates and stores the new settings, then applies them to the running models.
+#[tauri::command]
+pub async fn update_settings(
+    state: tauri::State<'_, AppState>,
+    mut settings: Settings,
+) -> CommandResult<Settings> {
+    settings.validate()?;
+
+    let mut current = state.settings.lock().await;
+    settings.provider.restore_api_key(&current.provider);
+
+    if settings.embedding_model != current.embedding_model {
+        // Vectors from different models can't be compared, so switching is only
+        // allowed before anything has been indexed.
+        let indexed = state.db.get_embeddings_repository().count_rows().await?;
+        if indexed > 0 {
+            return Err(CommandError {
+                reason: "The embedding model can't be changed while there are indexed files. Remove them first.".to_string(),
+            });
+        }
+    }
+
+    // Everything that can fail happens before the running models are touched, so a
+    // failed update leaves the app as it was.
+    let provider = settings.provider.build()?;
+    let mut embeddings_model = state.embeddings_model.lock().await;
+    let embedding_model = embeddings_model.load_for(&settings)?;
+
+    state.db.get_settings_repository().save(&settings).await?;
+
+    state.chat_model.lock().await.set_provider(provider);
+    embeddings_model.configure(&settings, embedding_model);
+    *current = settings;
+
+    Ok(current.redacted())
+}
+
 pub fn register_commands() -> impl Fn(tauri::ipc::Invoke<tauri::Wry>) -> bool {
     tauri::generate_handler![
         create_notebook,
@@ -300,8 +480,19 @@ pub fn register_commands() -> impl Fn(tauri::ipc::Invoke<tauri::Wry>) -> bool {
         delete_notebook,
         get_chat_history,
         get_attachments,
-        upload_file,
+        upload_files,
+        import_folder,
+        watch_folder,
+        get_watched_folders,
+        sync_watched_folder,
+        unwatch_folder,
+        cancel_ingestion,
+        reindex_attachment,
         delete_attachment,
-        chat
+        chat,
+        chat_stream,
+        search_notebook,
+        get_settings,
+        update_settings
     ]
 }
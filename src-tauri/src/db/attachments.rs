@@ -8,17 +8,62 @@ pub struct Attachment {
     pub id: String,
     pub notebook_id: String,
     pub file_name: String,
+    /// The managed copy in the file store.
     pub file_path: String,
+    /// Where the file was imported from. Empty for attachments imported before the
+    /// file store, whose `file_path` is still the original.
+    pub original_path: Option<String>,
+    /// SHA-256 of the file, as hex. Empty for attachments imported before it was stored.
+    pub content_hash: Option<String>,
+    /// Modification time of the original file when it was last copied, in seconds.
+    pub source_modified_at: Option<i64>,
+    /// The original file changed since it was indexed.
+    pub stale: bool,
+    /// The watched folder the file was synced from, if any.
+    pub watched_folder_id: Option<String>,
     pub file_size: i64,
     pub file_type: String,
     pub status: String,
     pub created_at: i64,
+    // Stored as a JSON object, filled in once the file has been read.
+    #[sqlx(json)]
+    pub metadata: AttachmentMetadata,
+}
+
+impl Attachment {
+    /// The path the user imported the file from.
+    pub fn source_path(&self) -> &str {
+        self.original_path.as_deref().unwrap_or(&self.file_path)
+    }
+}
+
+/// What the extractors could learn about a document besides its text.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct AttachmentMetadata {
+    pub title: Option<String>,
+    /// Where a saved web page came from.
+    pub url: Option<String>,
+}
+
+/// A file being imported, before it has an attachment record.
+pub struct NewAttachment {
+    pub notebook_id: String,
+    pub file_name: String,
+    pub file_path: String,
+    pub original_path: String,
+    pub content_hash: String,
+    pub source_modified_at: Option<i64>,
+    pub watched_folder_id: Option<String>,
+    pub file_size: i64,
+    pub file_type: String,
 }
 
 pub enum AttachmentStatus {
     Pending,
     Ready,
     Error,
+    Cancelled,
 }
 
 #[derive(Clone)]
@@ -35,31 +80,37 @@ impl AttachmentRepository {
     pub async fn create_with_tx(
         &self,
         tx: &mut sqlx::Transaction<'_, sqlx::Sqlite>,
-        notebook_id: String,
-        name: String,
-        path: String,
-        size: i64,
-        mime: String,
+        new: NewAttachment,
     ) -> Result<Attachment> {
         let attachment = Attachment {
             id: Uuid::new_v4().to_string(),
-            notebook_id,
-            file_name: name,
-            file_path: path,
-            file_size: size,
-            file_type: mime,
+            notebook_id: new.notebook_id,
+            file_name: new.file_name,
+            file_path: new.file_path,
+            original_path: Some(new.original_path),
+            content_hash: Some(new.content_hash),
+            source_modified_at: new.source_modified_at,
+            stale: false,
+            watched_folder_id: new.watched_folder_id,
+            file_size: new.file_size,
+            file_type: new.file_type,
             status: "pending".to_string(),
             created_at: chrono::Utc::now().timestamp(),
+            metadata: AttachmentMetadata::default(),
         };
 
         sqlx::query(
-            "INSERT INTO attachments (id, notebook_id, file_name, file_path, file_size, file_type, status, created_at)
-             VALUES (?, ?, ?, ?, ?, ?, ?, ?)"
+            "INSERT INTO attachments (id, notebook_id, file_name, file_path, original_path, content_hash, source_modified_at, watched_folder_id, file_size, file_type, status, created_at)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"
         )
         .bind(&attachment.id)
         .bind(&attachment.notebook_id)
         .bind(&attachment.file_name)
         .bind(&attachment.file_path)
+        .bind(&attachment.original_path)
+        .bind(&attachment.content_hash)
+        .bind(attachment.source_modified_at)
+        .bind(&attachment.watched_folder_id)
         .bind(attachment.file_size)
         .bind(&attachment.file_type)
         .bind(&attachment.status)
@@ -83,6 +134,148 @@ impl AttachmentRepository {
         Ok(files)
     }
 
+    pub async fn get_by_id(&self, id: &str) -> Result<Attachment> {
+        sqlx::query_as::<_, Attachment>("SELECT * FROM attachments WHERE id = ?")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?
+            .context(format!("Attachment with ID {} not found", id))
+    }
+
+    pub async fn exists(&self, id: &str) -> Result<bool> {
+        sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM attachments WHERE id = ?)")
+            .bind(id)
+            .fetch_one(&self.pool)
+            .await
+            .context("Failed to check whether the attachment exists")
+    }
+
+    /// An attachment of the notebook with the same content, if there is one.
+    pub async fn find_by_hash(
+        &self,
+        notebook_id: &str,
+        content_hash: &str,
+    ) -> Result<Option<Attachment>> {
+        sqlx::query_as::<_, Attachment>(
+            "SELECT * FROM attachments WHERE notebook_id = ? AND content_hash = ? AND status IN ('pending', 'ready') LIMIT 1",
+        )
+        .bind(notebook_id)
+        .bind(content_hash)
+        .fetch_optional(&self.pool)
+        .await
+        .context("Failed to look for a duplicate attachment")
+    }
+
+    /// The oldest other attachment with this content that has been indexed, in any notebook.
+    pub async fn find_ready_by_hash(
+        &self,
+        content_hash: &str,
+        except_id: &str,
+    ) -> Result<Option<Attachment>> {
+        sqlx::query_as::<_, Attachment>(
+            "SELECT * FROM attachments WHERE content_hash = ? AND id != ? AND status = 'ready' ORDER BY created_at ASC LIMIT 1",
+        )
+        .bind(content_hash)
+        .bind(except_id)
+        .fetch_optional(&self.pool)
+        .await
+        .context("Failed to look for an indexed copy of this file")
+    }
+
+    /// Attachments that still have to be processed, oldest first
+    pub async fn get_pending(&self) -> Result<Vec<Attachment>> {
+        let files = sqlx::query_as::<_, Attachment>(
+            "SELECT * FROM attachments WHERE status = 'pending' ORDER BY created_at ASC",
+        )
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to list pending attachments")?;
+
+        Ok(files)
+    }
+
+    /// How many attachments, in any notebook, point at this file.
+    pub async fn count_by_file_path(&self, file_path: &str) -> Result<i64> {
+        sqlx::query_scalar("SELECT COUNT(*) FROM attachments WHERE file_path = ?")
+            .bind(file_path)
+            .fetch_one(&self.pool)
+            .await
+            .context("Failed to count the attachments of a file")
+    }
+
+    /// Attachments whose original file can be checked for changes. Files of watched
+    /// folders are left out, syncing the folder takes care of them.
+    pub async fn get_watched(&self) -> Result<Vec<Attachment>> {
+        let files = sqlx::query_as::<_, Attachment>(
+            "SELECT * FROM attachments WHERE original_path IS NOT NULL AND watched_folder_id IS NULL AND status IN ('ready', 'error')",
+        )
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to list watched attachments")?;
+
+        Ok(files)
+    }
+
+    pub async fn get_by_watched_folder(&self, watched_folder_id: &str) -> Result<Vec<Attachment>> {
+        let files = sqlx::query_as::<_, Attachment>(
+            "SELECT * FROM attachments WHERE watched_folder_id = ?",
+        )
+        .bind(watched_folder_id)
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to list the files of this folder")?;
+
+        Ok(files)
+    }
+
+    /// Points the attachment at a new copy of its original file and marks it as
+    /// pending, so it's indexed again.
+    pub async fn update_source(
+        &self,
+        id: &str,
+        file_path: &str,
+        original_path: &str,
+        content_hash: &str,
+        file_size: i64,
+        source_modified_at: Option<i64>,
+    ) -> Result<()> {
+        sqlx::query(
+            "UPDATE attachments
+             SET file_path = ?, original_path = ?, content_hash = ?, file_size = ?, source_modified_at = ?, stale = 0, status = 'pending'
+             WHERE id = ?",
+        )
+        .bind(file_path)
+        .bind(original_path)
+        .bind(content_hash)
+        .bind(file_size)
+        .bind(source_modified_at)
+        .bind(id)
+        .execute(&self.pool)
+        .await
+        .context("Failed to update the source of this attachment.")?;
+        Ok(())
+    }
+
+    /// Records that the original file was touched without its content changing.
+    pub async fn update_source_modified_at(&self, id: &str, source_modified_at: i64) -> Result<()> {
+        sqlx::query("UPDATE attachments SET source_modified_at = ? WHERE id = ?")
+            .bind(source_modified_at)
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .context("Failed to update this attachment.")?;
+        Ok(())
+    }
+
+    pub async fn mark_stale(&self, id: &str) -> Result<()> {
+        sqlx::query("UPDATE attachments SET stale = 1 WHERE id = ?")
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .context("Failed to flag this attachment as out of date.")?;
+        Ok(())
+    }
+
     pub async fn delete_with_tx(
         &self,
         tx: &mut sqlx::Transaction<'_, sqlx::Sqlite>,
@@ -101,6 +294,7 @@ impl AttachmentRepository {
             AttachmentStatus::Pending => "pending",
             AttachmentStatus::Ready => "ready",
             AttachmentStatus::Error => "error",
+            AttachmentStatus::Cancelled => "cancelled",
         };
         sqlx::query("UPDATE attachments SET status = ? WHERE id = ?")
             .bind(status)
@@ -110,4 +304,21 @@ impl AttachmentRepository {
             .context("Failed to update this attachment status.")?;
         Ok(())
     }
+
+    pub async fn update_metadata(
+        &self,
+        attachment_id: &str,
+        metadata: &AttachmentMetadata,
+    ) -> Result<()> {
+        let metadata_json =
+            serde_json::to_string(metadata).context("Failed to serialize attachment metadata")?;
+
+        sqlx::query("UPDATE attachments SET metadata = ? WHERE id = ?")
+            .bind(metadata_json)
+            .bind(attachment_id)
+            .execute(&self.pool)
+            .await
+            .context("Failed to update this attachment metadata.")?;
+        Ok(())
+    }
 }
@@ -13,6 +13,27 @@ pub struct ChatEntry {
     pub role: String,
     pub message: String,
     pub timestamp: i64,
+    // Stored as a JSON array, only assistant messages have citations.
+    #[sqlx(json)]
+    pub citations: Vec<Citation>,
+}
+
+/// A retrieved chunk that backs an assistant answer. `marker` is the number used
+/// in the answer text, e.g. `[2]`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Citation {
+    pub marker: usize,
+    pub attachment_id: String,
+    pub file_name: String,
+    pub text: String,
+    pub score: f32,
+    pub page: Option<u32>,
+    /// Chapter, sheet or other named part of the document.
+    pub section: Option<String>,
+    pub row_start: Option<u32>,
+    pub row_end: Option<u32>,
+    pub line_start: Option<u32>,
+    pub line_end: Option<u32>,
 }
 
 #[derive(Clone)]
@@ -30,6 +51,17 @@ impl ChatEntryRepository {
         notebook_id: &str,
         role: MessageRole,
         message: String,
+    ) -> Result<ChatEntry> {
+        self.create_with_citations(notebook_id, role, message, Vec::new())
+            .await
+    }
+
+    pub async fn create_with_citations(
+        &self,
+        notebook_id: &str,
+        role: MessageRole,
+        message: String,
+        citations: Vec<Citation>,
     ) -> Result<ChatEntry> {
         let id = Uuid::new_v4().to_string();
         let now = chrono::Utc::now().timestamp();
@@ -42,15 +74,19 @@ impl ChatEntryRepository {
             MessageRole::Tool => "tool",
         };
 
+        let citations_json =
+            serde_json::to_string(&citations).context("Failed to serialize citations")?;
+
         sqlx::query(
-            "INSERT INTO chat_entries (id, notebook_id, role, message, timestamp)
-             VALUES (?, ?, ?, ?, ?)",
+            "INSERT INTO chat_entries (id, notebook_id, role, message, timestamp, citations)
+             VALUES (?, ?, ?, ?, ?, ?)",
         )
         .bind(&id)
         .bind(&notebook_id)
         .bind(role_str)
         .bind(&message)
         .bind(now)
+        .bind(citations_json)
         .execute(&self.pool)
         .await
         .context("Failed to save chat message")?;
@@ -61,6 +97,7 @@ impl ChatEntryRepository {
             role: role_str.to_string(),
             message,
             timestamp: now,
+            citations,
         })
     }
 
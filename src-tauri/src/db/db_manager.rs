@@ -3,7 +3,8 @@ use sqlx::{sqlite::SqlitePoolOptions, Pool, Sqlite, Transaction};
 
 use crate::db::{
     attachments::AttachmentRepository, chat::ChatEntryRepository, embeddings::EmbeddingsRepository,
-    notebooks::NotebookRepository,
+    ingestion_jobs::IngestionJobRepository, notebooks::NotebookRepository,
+    settings::SettingsRepository, watched_folders::WatchedFolderRepository,
 };
 
 #[derive(Clone)]
@@ -12,6 +13,9 @@ pub struct DBManager {
     chat_repository: ChatEntryRepository,
     attachments_repository: AttachmentRepository,
     embeddings_repository: EmbeddingsRepository,
+    settings_repository: SettingsRepository,
+    ingestion_jobs_repository: IngestionJobRepository,
+    watched_folders_repository: WatchedFolderRepository,
     sqlite: Pool<Sqlite>,
 }
 
@@ -38,12 +42,18 @@ impl DBManager {
         let attachments = AttachmentRepository::new(sqlite.clone());
         let chats = ChatEntryRepository::new(sqlite.clone());
         let emebddings = EmbeddingsRepository::new(lancedb_conn);
+        let settings = SettingsRepository::new(sqlite.clone());
+        let ingestion_jobs = IngestionJobRepository::new(sqlite.clone());
+        let watched_folders = WatchedFolderRepository::new(sqlite.clone());
 
         Ok(Self {
             notebooks_repository: notebooks,
             chat_repository: chats,
             attachments_repository: attachments,
             embeddings_repository: emebddings,
+            settings_repository: settings,
+            ingestion_jobs_repository: ingestion_jobs,
+            watched_folders_repository: watched_folders,
             sqlite: sqlite,
         })
     }
@@ -64,6 +74,18 @@ impl DBManager {
         &self.embeddings_repository
     }
 
+    pub fn get_settings_repository(&self) -> &SettingsRepository {
+        &self.settings_repository
+    }
+
+    pub fn get_ingestion_jobs_repository(&self) -> &IngestionJobRepository {
+        &self.ingestion_jobs_repository
+    }
+
+    pub fn get_watched_folders_repository(&self) -> &WatchedFolderRepository {
+        &self.watched_folders_repository
+    }
+
     pub async fn begin_transaction(&self) -> Result<SqliteTransaction<'_>> {
         self.sqlite
             .begin()
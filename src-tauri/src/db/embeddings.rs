@@ -1,20 +1,112 @@
-use std::sync::Arc;
+use std::{collections::HashMap, sync::Arc};
 
 use anyhow::{Context, Result};
-use arrow_array::{Float32Array, RecordBatch, StringArray};
+use arrow_array::{
+    Array, ArrayRef, FixedSizeListArray, Float32Array, Int32Array, RecordBatch, StringArray,
+};
 use arrow_schema::{DataType, Field, Schema};
 use futures::TryStreamExt;
-use lancedb::query::{ExecutableQuery, QueryBase};
+use lancedb::{
+    index::{
+        scalar::{FtsIndexBuilder, FullTextSearchQuery},
+        Index, IndexType,
+    },
+    query::{ExecutableQuery, QueryBase},
+    table::NewColumnTransform,
+};
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize)]
+use crate::db::filter::{Column, Filter};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct VectorSearchResult {
     pub text: String,
     pub attachment_id: String,
     pub file_path: String,
+    /// Named part of the document the chunk comes from, like a chapter or a sheet.
+    pub section: Option<String>,
+    pub row_start: Option<u32>,
+    pub row_end: Option<u32>,
+    pub line_start: Option<u32>,
+    pub line_end: Option<u32>,
+    /// PDF page, starting at 1.
+    pub page: Option<u32>,
+    /// Higher is better: cosine similarity for vector search, fused rank score for hybrid
+    /// search, cross-encoder score once reranked.
     pub score: f32,
+    /// Position of the chunk in its document, and its character range in the text.
+    #[serde(skip)]
+    pub chunk_index: Option<u32>,
+    #[serde(skip)]
+    pub start_offset: Option<u32>,
+    #[serde(skip)]
+    pub end_offset: Option<u32>,
+    /// Embedding of the chunk, used to tell similar results apart.
+    #[serde(skip)]
+    pub vector: Vec<f32>,
 }
 
+impl VectorSearchResult {
+    /// Where the chunk is in its document, e.g. "p. 12", "Sheet1, rows 40–60" or
+    /// "fn main, lines 3–25".
+    pub fn location(&self) -> Option<String> {
+        let mut labels = Vec::new();
+
+        if let Some(page) = self.page {
+            labels.push(format!("p. {}", page));
+        }
+
+        if let Some(section) = &self.section {
+            labels.push(section.clone());
+        }
+
+        labels.extend(Self::range_label(
+            "row",
+            "rows",
+            self.row_start,
+            self.row_end,
+        ));
+        labels.extend(Self::range_label(
+            "line",
+            "lines",
+            self.line_start,
+            self.line_end,
+        ));
+
+        (!labels.is_empty()).then(|| labels.join(", "))
+    }
+
+    fn range_label(
+        singular: &str,
+        plural: &str,
+        start: Option<u32>,
+        end: Option<u32>,
+    ) -> Option<String> {
+        match (start, end) {
+            (Some(start), Some(end)) if start != end => {
+                Some(format!("{} {}–{}", plural, start, end))
+            }
+            (Some(start), _) => Some(format!("{} {}", singular, start)),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SearchMode {
+    /// Nearest neighbours on the embeddings only.
+    #[default]
+    Vector,
+    /// Vector search combined with BM25 full-text search, which catches exact
+    /// names, codes and dates that embeddings tend to miss.
+    Hybrid,
+}
+
+// Constant from the original reciprocal rank fusion paper, it keeps the first few
+// ranks of a single list from dominating the fused ranking.
+const RRF_K: f32 = 60.0;
+
 #[derive(Clone)]
 pub struct EmbeddingsRepository {
     conn: lancedb::Connection,
@@ -35,30 +127,108 @@ impl EmbeddingsRepository {
         let reader = arrow_array::RecordBatchIterator::new(vec![Ok(batch)], schema);
 
         table.add(reader).execute().await?;
+        self.ensure_fts_index(&table).await?;
         Ok(())
     }
 
     pub async fn remove_document_embeddings(&self, attachment_id: &str) -> Result<()> {
         let table = self
-            .conn
-            .open_table(Self::TABLE_NAME)
-            .execute()
+            .get_or_create_table()
             .await
             .context("Failed to open embeddings table for deletion")?;
 
         // LanceDB allows deleting rows based on a SQL-like filter
         table
-            .delete(&format!("attachment_id = '{}'", attachment_id))
+            .delete(
+                &Filter::new()
+                    .equals(Column::AttachmentId, attachment_id)
+                    .build(),
+            )
             .await
             .context("Failed to delete embeddings from LanceDB")?;
 
         Ok(())
     }
 
-    /// Perform Vector Search scoped to a specific Notebook
+    /// Copies the embeddings of an attachment to another one with the same content,
+    /// so a file imported in several notebooks is only embedded once. Returns how
+    /// many chunks were copied.
+    pub async fn copy_document(
+        &self,
+        source_attachment_id: &str,
+        attachment_id: &str,
+        notebook_id: &str,
+        path: &str,
+    ) -> Result<usize> {
+        let table = self.get_or_create_table().await?;
+        let filter = Filter::new()
+            .equals(Column::AttachmentId, source_attachment_id)
+            .build();
+
+        let count = table
+            .count_rows(Some(filter.clone()))
+            .await
+            .context("Failed to count the embeddings to copy")?;
+        if count == 0 {
+            return Ok(0);
+        }
+
+        let batches = table
+            .query()
+            .only_if(filter)
+            .limit(count)
+            .execute()
+            .await
+            .context("Failed to read the embeddings to copy")?
+            .try_collect::<Vec<_>>()
+            .await?;
+
+        let mut copies = Vec::new();
+        for batch in batches {
+            let rows = batch.num_rows();
+            let schema = batch.schema();
+
+            let columns: Vec<ArrayRef> = schema
+                .fields()
+                .iter()
+                .zip(batch.columns())
+                .map(|(field, column)| -> ArrayRef {
+                    match field.name().as_str() {
+                        "attachment_id" => Arc::new(StringArray::from(vec![attachment_id; rows])),
+                        "notebook_id" => Arc::new(StringArray::from(vec![notebook_id; rows])),
+                        "path" => Arc::new(StringArray::from(vec![path; rows])),
+                        _ => column.clone(),
+                    }
+                })
+                .collect();
+
+            copies.push(RecordBatch::try_new(schema, columns)?);
+        }
+
+        let Some(schema) = copies.first().map(|b| b.schema()) else {
+            return Ok(0);
+        };
+        let reader = arrow_array::RecordBatchIterator::new(copies.into_iter().map(Ok), schema);
+
+        table.add(reader).execute().await?;
+        self.ensure_fts_index(&table).await?;
+        Ok(count)
+    }
+
+    pub async fn count_rows(&self) -> Result<usize> {
+        let table = self.get_or_create_table().await?;
+
+        table
+            .count_rows(None)
+            .await
+            .context("Failed to count rows in the embeddings table")
+    }
+
+    /// Perform Vector Search scoped to a specific Notebook, and optionally to some of its attachments
     pub async fn search(
         &self,
         notebook_id: &str,
+        attachment_ids: Option<&[String]>,
         query_vector: Vec<f32>,
         limit: usize,
     ) -> Result<Vec<VectorSearchResult>> {
@@ -67,7 +237,7 @@ impl EmbeddingsRepository {
         let batches = table
             .query()
             .limit(limit)
-            .only_if(format!("notebook_id = '{}'", notebook_id))
+            .only_if(Self::scope_filter(notebook_id, attachment_ids))
             .nearest_to(query_vector)?
             .distance_type(lancedb::DistanceType::Cosine)
             .execute()
@@ -84,6 +254,122 @@ impl EmbeddingsRepository {
         Ok(results)
     }
 
+    /// Hybrid search scoped to a notebook. Both searches over-fetch candidates, which
+    /// are then merged with reciprocal rank fusion.
+    pub async fn hybrid_search(
+        &self,
+        notebook_id: &str,
+        attachment_ids: Option<&[String]>,
+        query_text: &str,
+        query_vector: Vec<f32>,
+        limit: usize,
+    ) -> Result<Vec<VectorSearchResult>> {
+        let candidates = (limit * 4).max(20);
+        let mut vector_hits = self
+            .search(notebook_id, attachment_ids, query_vector, candidates)
+            .await?;
+
+        let table = self.get_or_create_table().await?;
+        if !self.has_fts_index(&table).await? {
+            // Nothing was indexed for full-text search yet.
+            vector_hits.truncate(limit);
+            return Ok(vector_hits);
+        }
+
+        let batches = table
+            .query()
+            .full_text_search(FullTextSearchQuery::new(query_text.to_string()))
+            .only_if(Self::scope_filter(notebook_id, attachment_ids))
+            .limit(candidates)
+            .execute()
+            .await
+            .context("Could not perform full-text search correctly.")?
+            .try_collect::<Vec<_>>()
+            .await?;
+
+        let mut text_hits = Vec::new();
+        for batch in batches {
+            text_hits.extend(self.parse_search_batch(batch)?);
+        }
+
+        Ok(Self::reciprocal_rank_fusion(
+            vec![vector_hits, text_hits],
+            limit,
+        ))
+    }
+
+    fn scope_filter(notebook_id: &str, attachment_ids: Option<&[String]>) -> String {
+        let filter = Filter::new().equals(Column::NotebookId, notebook_id);
+        match attachment_ids {
+            Some(attachment_ids) => filter.any_of(Column::AttachmentId, attachment_ids),
+            None => filter,
+        }
+        .build()
+    }
+
+    /// Chunks `first` to `last` of an attachment, in document order.
+    pub async fn get_chunks(
+        &self,
+        attachment_id: &str,
+        first: u32,
+        last: u32,
+    ) -> Result<Vec<VectorSearchResult>> {
+        let table = self.get_or_create_table().await?;
+
+        let batches = table
+            .query()
+            .only_if(
+                Filter::new()
+                    .equals(Column::AttachmentId, attachment_id)
+                    .between(Column::ChunkIndex, first, last)
+                    .build(),
+            )
+            .limit((last - first + 1) as usize)
+            .execute()
+            .await
+            .context("Could not read the neighbouring chunks.")?
+            .try_collect::<Vec<_>>()
+            .await?;
+
+        let mut chunks = Vec::new();
+        for batch in batches {
+            chunks.extend(self.parse_search_batch(batch)?);
+        }
+        chunks.sort_by_key(|c| c.chunk_index);
+
+        Ok(chunks)
+    }
+
+    /// Merges ranked lists: every hit scores `1 / (RRF_K + rank)` in each list it
+    /// shows up in, and hits are sorted by the sum.
+    fn reciprocal_rank_fusion(
+        rankings: Vec<Vec<VectorSearchResult>>,
+        limit: usize,
+    ) -> Vec<VectorSearchResult> {
+        let mut fused: Vec<VectorSearchResult> = Vec::new();
+        let mut positions: HashMap<(String, String), usize> = HashMap::new();
+
+        for ranking in rankings {
+            for (rank, mut hit) in ranking.into_iter().enumerate() {
+                let score = 1.0 / (RRF_K + rank as f32 + 1.0);
+                let key = (hit.attachment_id.clone(), hit.text.clone());
+
+                match positions.get(&key) {
+                    Some(&i) => fused[i].score += score,
+                    None => {
+                        hit.score = score;
+                        positions.insert(key, fused.len());
+                        fused.push(hit);
+                    }
+                }
+            }
+        }
+
+        fused.sort_by(|a, b| b.score.total_cmp(&a.score));
+        fused.truncate(limit);
+        fused
+    }
+
     fn parse_search_batch(&self, batch: RecordBatch) -> Result<Vec<VectorSearchResult>> {
         let text_array = batch
             .column_by_name("text")
@@ -106,25 +392,85 @@ impl EmbeddingsRepository {
             .downcast_ref::<StringArray>()
             .context("Failed to downcast 'attachment_id' column")?;
 
-        let score_array = batch
-            .column_by_name("_distance")
-            .context("Missing '_distance' column")?
-            .as_any()
-            .downcast_ref::<Float32Array>()
-            .context("Failed to downcast '_distance' column")?;
+        // Location columns are null for formats that don't have them.
+        let section_array = Self::optional_column::<StringArray>(&batch, "section");
+        let row_start_array = Self::optional_column::<Int32Array>(&batch, "row_start");
+        let row_end_array = Self::optional_column::<Int32Array>(&batch, "row_end");
+        let line_start_array = Self::optional_column::<Int32Array>(&batch, "line_start");
+        let line_end_array = Self::optional_column::<Int32Array>(&batch, "line_end");
+        let page_array = Self::optional_column::<Int32Array>(&batch, "page");
+        let vector_array = Self::optional_column::<FixedSizeListArray>(&batch, "vector");
+
+        // Vector searches return a distance, full-text searches a BM25 score and
+        // plain queries neither.
+        let distance_array = Self::optional_column::<Float32Array>(&batch, "_distance");
+        let bm25_array = Self::optional_column::<Float32Array>(&batch, "_score");
+
+        let chunk_index_array = Self::optional_column::<Int32Array>(&batch, "chunk_index");
+        let start_offset_array = Self::optional_column::<Int32Array>(&batch, "start_offset");
+        let end_offset_array = Self::optional_column::<Int32Array>(&batch, "end_offset");
 
         let mut results = Vec::new();
         for i in 0..batch.num_rows() {
+            let score = match (distance_array, bm25_array) {
+                (Some(distances), _) => 1.0 - distances.value(i),
+                (None, Some(scores)) => scores.value(i),
+                (None, None) => 0.0,
+            };
+
             results.push(VectorSearchResult {
                 text: text_array.value(i).to_string(),
                 attachment_id: attachment_id_array.value(i).to_string(),
-                score: score_array.value(i),
+                score,
                 file_path: file_path.value(i).to_string(),
+                section: section_array
+                    .filter(|a| a.is_valid(i))
+                    .map(|a| a.value(i).to_string()),
+                row_start: row_start_array
+                    .filter(|a| a.is_valid(i))
+                    .map(|a| a.value(i) as u32),
+                row_end: row_end_array
+                    .filter(|a| a.is_valid(i))
+                    .map(|a| a.value(i) as u32),
+                line_start: line_start_array
+                    .filter(|a| a.is_valid(i))
+                    .map(|a| a.value(i) as u32),
+                line_end: line_end_array
+                    .filter(|a| a.is_valid(i))
+                    .map(|a| a.value(i) as u32),
+                page: page_array
+                    .filter(|a| a.is_valid(i))
+                    .map(|a| a.value(i) as u32),
+                chunk_index: chunk_index_array
+                    .filter(|a| a.is_valid(i))
+                    .map(|a| a.value(i) as u32),
+                start_offset: start_offset_array
+                    .filter(|a| a.is_valid(i))
+                    .map(|a| a.value(i) as u32),
+                end_offset: end_offset_array
+                    .filter(|a| a.is_valid(i))
+                    .map(|a| a.value(i) as u32),
+                vector: vector_array
+                    .filter(|a| a.is_valid(i))
+                    .and_then(|a| {
+                        a.value(i)
+                            .as_any()
+                            .downcast_ref::<Float32Array>()
+                            .map(|v| v.values().to_vec())
+                    })
+                    .unwrap_or_default(),
             });
         }
 
         Ok(results)
     }
+
+    fn optional_column<'a, T: 'static>(batch: &'a RecordBatch, name: &str) -> Option<&'a T> {
+        batch
+            .column_by_name(name)
+            .and_then(|c| c.as_any().downcast_ref::<T>())
+    }
+
     async fn get_schema(&self) -> Arc<Schema> {
         let dim = 384;
         Arc::new(Schema::new(vec![
@@ -132,6 +478,16 @@ impl EmbeddingsRepository {
             Field::new("notebook_id", DataType::Utf8, false),
             Field::new("path", DataType::Utf8, false),
             Field::new("text", DataType::Utf8, false),
+            Field::new("chunk_index", DataType::Int32, true),
+            Field::new("start_offset", DataType::Int32, true),
+            Field::new("end_offset", DataType::Int32, true),
+            Field::new("heading", DataType::Utf8, true),
+            Field::new("section", DataType::Utf8, true),
+            Field::new("row_start", DataType::Int32, true),
+            Field::new("row_end", DataType::Int32, true),
+            Field::new("line_start", DataType::Int32, true),
+            Field::new("line_end", DataType::Int32, true),
+            Field::new("page", DataType::Int32, true),
             Field::new(
                 "vector",
                 DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Float32, true)), dim),
@@ -140,11 +496,73 @@ impl EmbeddingsRepository {
         ]))
     }
 
+    /// Tables created by older versions lack the newer metadata columns. They are
+    /// added filled with nulls so new batches still fit the table.
+    async fn add_missing_columns(&self, table: &lancedb::Table) -> Result<()> {
+        let existing = table.schema().await?;
+
+        let mut expressions = Vec::new();
+        for field in self.get_schema().await.fields() {
+            if existing.field_with_name(field.name()).is_ok() {
+                continue;
+            }
+
+            let sql_type = match field.data_type() {
+                DataType::Int32 => "INT",
+                DataType::Float32 => "FLOAT",
+                DataType::Utf8 => "STRING",
+                other => anyhow::bail!("Can't add a column of type {} to the table", other),
+            };
+
+            expressions.push((
+                field.name().to_string(),
+                format!("CAST(NULL AS {})", sql_type),
+            ));
+        }
+
+        if expressions.is_empty() {
+            return Ok(());
+        }
+
+        table
+            .add_columns(NewColumnTransform::SqlExpressions(expressions), None)
+            .await
+            .context("Failed to add the missing columns to the embeddings table")?;
+
+        Ok(())
+    }
+
+    async fn has_fts_index(&self, table: &lancedb::Table) -> Result<bool> {
+        let indices = table.list_indices().await?;
+
+        Ok(indices
+            .iter()
+            .any(|index| index.index_type == IndexType::FTS && index.columns == ["text"]))
+    }
+
+    /// The full-text index is only built once the table has rows. Rows added after
+    /// that are still searched, LanceDB scans unindexed data alongside the index.
+    async fn ensure_fts_index(&self, table: &lancedb::Table) -> Result<()> {
+        if self.has_fts_index(table).await? {
+            return Ok(());
+        }
+
+        table
+            .create_index(&["text"], Index::FTS(FtsIndexBuilder::default()))
+            .execute()
+            .await
+            .context("Failed to create full-text index on text")?;
+
+        Ok(())
+    }
+
     async fn get_or_create_table(&self) -> Result<lancedb::Table> {
         let table_names = self.conn.table_names().execute().await?;
 
         let table = if table_names.contains(&Self::TABLE_NAME.to_string()) {
-            self.conn.open_table(Self::TABLE_NAME).execute().await?
+            let table = self.conn.open_table(Self::TABLE_NAME).execute().await?;
+            self.add_missing_columns(&table).await?;
+            table
         } else {
             self.conn
                 .create_empty_table(Self::TABLE_NAME, self.get_schema().await)
@@ -0,0 +1,277 @@
+use std::fmt::Write;
+
+/// Columns of the embeddings table that queries filter on. Only these can end up in
+/// a filter, so a column name never comes from a caller's string.
+// The location columns aren't filtered on by any query yet.
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Column {
+    AttachmentId,
+    NotebookId,
+    ChunkIndex,
+    Page,
+    RowStart,
+    RowEnd,
+    LineStart,
+    LineEnd,
+}
+
+impl Column {
+    fn name(self) -> &'static str {
+        match self {
+            Self::AttachmentId => "attachment_id",
+            Self::NotebookId => "notebook_id",
+            Self::ChunkIndex => "chunk_index",
+            Self::Page => "page",
+            Self::RowStart => "row_start",
+            Self::RowEnd => "row_end",
+            Self::LineStart => "line_start",
+            Self::LineEnd => "line_end",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Text(String),
+    Int(i64),
+}
+
+impl Value {
+    /// Writes the value as a SQL literal. Quotes in text are doubled; backslashes
+    /// aren't escape characters in LanceDB filters, so they're kept as they are.
+    fn write_literal(&self, out: &mut String) {
+        match self {
+            Self::Text(text) => {
+                out.push('\'');
+                out.push_str(&text.replace('\'', "''"));
+                out.push('\'');
+            }
+            Self::Int(value) => {
+                write!(out, "{}", value).ok();
+            }
+        }
+    }
+}
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        Self::Text(value.to_string())
+    }
+}
+
+impl From<&String> for Value {
+    fn from(value: &String) -> Self {
+        Self::Text(value.clone())
+    }
+}
+
+impl From<u32> for Value {
+    fn from(value: u32) -> Self {
+        Self::Int(value.into())
+    }
+}
+
+/// Builds the SQL predicates passed to LanceDB, with every value escaped. Conditions
+/// are combined with `AND`.
+#[derive(Debug, Clone, Default)]
+pub struct Filter {
+    conditions: Vec<String>,
+}
+
+impl Filter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// `column = value`
+    pub fn equals(mut self, column: Column, value: impl Into<Value>) -> Self {
+        let mut condition = format!("{} = ", column.name());
+        value.into().write_literal(&mut condition);
+        self.conditions.push(condition);
+        self
+    }
+
+    /// `column IN (values)`. An empty list matches nothing.
+    pub fn any_of<V: Into<Value>>(
+        mut self,
+        column: Column,
+        values: impl IntoIterator<Item = V>,
+    ) -> Self {
+        let mut literals = String::new();
+        for value in values {
+            if !literals.is_empty() {
+                literals.push_str(", ");
+            }
+            value.into().write_literal(&mut literals);
+        }
+
+        let condition = if literals.is_empty() {
+            "false".to_string()
+        } else {
+            format!("{} IN ({})", column.name(), literals)
+        };
+        self.conditions.push(condition);
+        self
+    }
+
+    /// `column >= first AND column <= last`
+    pub fn between(
+        mut self,
+        column: Column,
+        first: impl Into<Value>,
+        last: impl Into<Value>,
+    ) -> Self {
+        let mut condition = format!("{} >= ", column.name());
+        first.into().write_literal(&mut condition);
+        write!(condition, " AND {} <= ", column.name()).ok();
+        last.into().write_literal(&mut condition);
+        self.conditions.push(condition);
+        self
+    }
+
+    /// The predicate, `true` when no condition was added.
+    pub fn build(&self) -> String {
+        if self.conditions.is_empty() {
+            "true".to_string()
+        } else {
+            self.conditions.join(" AND ")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow_array::{Int32Array, RecordBatch, RecordBatchIterator, StringArray};
+    use arrow_schema::{DataType, Field, Schema};
+
+    use super::*;
+
+    #[test]
+    fn quotes_in_values_are_doubled() {
+        let filter = Filter::new().equals(Column::AttachmentId, "it's").build();
+
+        assert_eq!(filter, "attachment_id = 'it''s'");
+    }
+
+    #[test]
+    fn injected_predicates_stay_inside_the_literal() {
+        let filter = Filter::new()
+            .equals(Column::NotebookId, "x' OR '1'='1")
+            .build();
+
+        assert_eq!(filter, "notebook_id = 'x'' OR ''1''=''1'");
+    }
+
+    #[test]
+    fn backslashes_are_kept_as_they_are() {
+        let filter = Filter::new().equals(Column::NotebookId, r"a\'b\\").build();
+
+        assert_eq!(filter, r"notebook_id = 'a\''b\\'");
+    }
+
+    #[test]
+    fn any_of_escapes_every_value() {
+        let ids = vec!["a".to_string(), "x' OR '1'='1".to_string()];
+        let filter = Filter::new()
+            .equals(Column::NotebookId, "n")
+            .any_of(Column::AttachmentId, &ids)
+            .build();
+
+        assert_eq!(
+            filter,
+            "notebook_id = 'n' AND attachment_id IN ('a', 'x'' OR ''1''=''1')"
+        );
+    }
+
+    #[test]
+    fn empty_any_of_matches_nothing() {
+        let filter = Filter::new()
+            .any_of(Column::AttachmentId, &Vec::<String>::new())
+            .build();
+
+        assert_eq!(filter, "false");
+    }
+
+    #[test]
+    fn between_is_inclusive() {
+        let filter = Filter::new()
+            .equals(Column::AttachmentId, "a")
+            .between(Column::ChunkIndex, 2u32, 5u32)
+            .build();
+
+        assert_eq!(
+            filter,
+            "attachment_id = 'a' AND chunk_index >= 2 AND chunk_index <= 5"
+        );
+    }
+
+    #[test]
+    fn between_works_on_locations() {
+        let filter = Filter::new()
+            .between(Column::Page, 3u32, 4u32)
+            .between(Column::LineStart, 10u32, 20u32)
+            .build();
+
+        assert_eq!(
+            filter,
+            "page >= 3 AND page <= 4 AND line_start >= 10 AND line_start <= 20"
+        );
+    }
+
+    #[test]
+    fn no_condition_matches_everything() {
+        assert_eq!(Filter::new().build(), "true");
+    }
+
+    #[tokio::test]
+    async fn lancedb_only_matches_the_intended_row() {
+        let dir = std::env::temp_dir().join(format!("filter-{}", uuid::Uuid::new_v4()));
+        let conn = lancedb::connect(&dir.to_string_lossy())
+            .execute()
+            .await
+            .unwrap();
+
+        let ids = ["a", "a'b", r"a\'b", r"a\b", r"a\", r"a\\", "x' OR '1'='1"];
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("attachment_id", DataType::Utf8, false),
+            Field::new("page", DataType::Int32, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(StringArray::from(ids.to_vec())),
+                Arc::new(Int32Array::from_iter_values(1..=ids.len() as i32)),
+            ],
+        )
+        .unwrap();
+
+        let table = conn
+            .create_empty_table("embeddings", schema.clone())
+            .execute()
+            .await
+            .unwrap();
+        table
+            .add(RecordBatchIterator::new(vec![Ok(batch)], schema))
+            .execute()
+            .await
+            .unwrap();
+
+        for id in ids {
+            let filter = Filter::new().equals(Column::AttachmentId, id).build();
+            let count = table.count_rows(Some(filter)).await.unwrap();
+            assert_eq!(count, 1, "{}", id);
+        }
+
+        // "a'b" is on page 2, "a\'b" on page 3.
+        let filter = Filter::new()
+            .any_of(Column::AttachmentId, ["a'b", r"a\'b"])
+            .between(Column::Page, 3u32, 7u32)
+            .build();
+        assert_eq!(table.count_rows(Some(filter)).await.unwrap(), 1);
+
+        std::fs::remove_dir_all(dir).ok();
+    }
+}
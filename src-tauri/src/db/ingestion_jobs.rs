@@ -0,0 +1,152 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, SqlitePool};
+use uuid::Uuid;
+
+#[derive(Serialize, Deserialize, Debug, Clone, FromRow)]
+pub struct IngestionJob {
+    pub id: String,
+    pub attachment_id: String,
+    pub notebook_id: String,
+    pub status: String,
+    pub attempts: i64,
+    pub error: Option<String>,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+pub enum IngestionJobStatus {
+    Queued,
+    Running,
+    Done,
+    Failed,
+    Cancelled,
+}
+
+impl IngestionJobStatus {
+    fn as_str(&self) -> &'static str {
+        match self {
+            IngestionJobStatus::Queued => "queued",
+            IngestionJobStatus::Running => "running",
+            IngestionJobStatus::Done => "done",
+            IngestionJobStatus::Failed => "failed",
+            IngestionJobStatus::Cancelled => "cancelled",
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct IngestionJobRepository {
+    pool: SqlitePool,
+}
+
+impl IngestionJobRepository {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    /// Queues a new job, in the same transaction that created the attachment
+    pub async fn create_with_tx(
+        &self,
+        tx: &mut sqlx::Transaction<'_, sqlx::Sqlite>,
+        attachment_id: &str,
+        notebook_id: &str,
+    ) -> Result<IngestionJob> {
+        let now = chrono::Utc::now().timestamp();
+        let job = IngestionJob {
+            id: Uuid::new_v4().to_string(),
+            attachment_id: attachment_id.to_string(),
+            notebook_id: notebook_id.to_string(),
+            status: IngestionJobStatus::Queued.as_str().to_string(),
+            attempts: 0,
+            error: None,
+            created_at: now,
+            updated_at: now,
+        };
+
+        sqlx::query(
+            "INSERT INTO ingestion_jobs (id, attachment_id, notebook_id, status, attempts, error, created_at, updated_at)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(&job.id)
+        .bind(&job.attachment_id)
+        .bind(&job.notebook_id)
+        .bind(&job.status)
+        .bind(job.attempts)
+        .bind(&job.error)
+        .bind(job.created_at)
+        .bind(job.updated_at)
+        .execute(&mut **tx)
+        .await
+        .context("Failed to create ingestion job")?;
+
+        Ok(job)
+    }
+
+    pub async fn create(&self, attachment_id: &str, notebook_id: &str) -> Result<IngestionJob> {
+        let mut tx = self
+            .pool
+            .begin()
+            .await
+            .context("Failed to start a new database transaction")?;
+
+        let job = self
+            .create_with_tx(&mut tx, attachment_id, notebook_id)
+            .await?;
+
+        tx.commit()
+            .await
+            .context("Failed to commit database transaction")?;
+
+        Ok(job)
+    }
+
+    /// The most recent job of an attachment
+    pub async fn get_latest_by_attachment(
+        &self,
+        attachment_id: &str,
+    ) -> Result<Option<IngestionJob>> {
+        let job = sqlx::query_as::<_, IngestionJob>(
+            "SELECT * FROM ingestion_jobs WHERE attachment_id = ? ORDER BY created_at DESC LIMIT 1",
+        )
+        .bind(attachment_id)
+        .fetch_optional(&self.pool)
+        .await
+        .context("Failed to fetch ingestion job")?;
+
+        Ok(job)
+    }
+
+    /// Marks the job as running and counts a new attempt
+    pub async fn start_attempt(&self, id: &str) -> Result<()> {
+        sqlx::query(
+            "UPDATE ingestion_jobs SET status = ?, attempts = attempts + 1, updated_at = ? WHERE id = ?",
+        )
+        .bind(IngestionJobStatus::Running.as_str())
+        .bind(chrono::Utc::now().timestamp())
+        .bind(id)
+        .execute(&self.pool)
+        .await
+        .context("Failed to start ingestion job")?;
+
+        Ok(())
+    }
+
+    pub async fn update_status(
+        &self,
+        id: &str,
+        status: IngestionJobStatus,
+        error: Option<String>,
+    ) -> Result<()> {
+        sqlx::query("UPDATE ingestion_jobs SET status = ?, error = ?, updated_at = ? WHERE id = ?")
+            .bind(status.as_str())
+            .bind(error)
+            .bind(chrono::Utc::now().timestamp())
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .context("Failed to update this ingestion job status.")?;
+
+        Ok(())
+    }
+}
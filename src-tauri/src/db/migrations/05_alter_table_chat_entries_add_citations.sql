@@ -0,0 +1 @@
+ALTER TABLE chat_entries ADD COLUMN citations TEXT NOT NULL DEFAULT '[]';
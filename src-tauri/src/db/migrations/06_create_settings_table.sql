@@ -0,0 +1,4 @@
+CREATE TABLE IF NOT EXISTS settings (
+    id INTEGER PRIMARY KEY NOT NULL CHECK (id = 1),
+    value TEXT NOT NULL -- JSON encoded Settings
+);
@@ -0,0 +1,12 @@
+CREATE TABLE IF NOT EXISTS ingestion_jobs (
+    id TEXT PRIMARY KEY NOT NULL,
+    attachment_id TEXT NOT NULL,
+    notebook_id TEXT NOT NULL,
+    status TEXT NOT NULL, -- "queued", "running", "done", "failed", "cancelled"
+    attempts INTEGER NOT NULL DEFAULT 0,
+    error TEXT,
+    created_at INTEGER NOT NULL,
+    updated_at INTEGER NOT NULL,
+    FOREIGN KEY(attachment_id) REFERENCES attachments(id) ON DELETE CASCADE
+);
+CREATE INDEX IF NOT EXISTS idx_ingestion_jobs_attachment_id ON ingestion_jobs(attachment_id);
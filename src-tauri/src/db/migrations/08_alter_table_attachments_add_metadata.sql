@@ -0,0 +1 @@
+ALTER TABLE attachments ADD COLUMN metadata TEXT NOT NULL DEFAULT '{}';
@@ -0,0 +1 @@
+ALTER TABLE attachments ADD COLUMN original_path TEXT;
@@ -0,0 +1,2 @@
+ALTER TABLE attachments ADD COLUMN content_hash TEXT;
+CREATE INDEX IF NOT EXISTS idx_attachments_content_hash ON attachments(content_hash);
@@ -0,0 +1,2 @@
+ALTER TABLE attachments ADD COLUMN source_modified_at INTEGER;
+ALTER TABLE attachments ADD COLUMN stale INTEGER NOT NULL DEFAULT 0;
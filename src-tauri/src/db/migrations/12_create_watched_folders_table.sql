@@ -0,0 +1,13 @@
+CREATE TABLE IF NOT EXISTS watched_folders (
+    id TEXT PRIMARY KEY NOT NULL,
+    notebook_id TEXT NOT NULL,
+    path TEXT NOT NULL,
+    created_at INTEGER NOT NULL,
+    last_synced_at INTEGER,
+    last_error TEXT,
+    FOREIGN KEY(notebook_id) REFERENCES notebooks(id) ON DELETE CASCADE,
+    UNIQUE(notebook_id, path)
+);
+CREATE INDEX IF NOT EXISTS idx_watched_folders_notebook_id ON watched_folders(notebook_id);
+
+ALTER TABLE attachments ADD COLUMN watched_folder_id TEXT REFERENCES watched_folders(id) ON DELETE SET NULL;
@@ -0,0 +1 @@
+ALTER TABLE watched_folders ADD COLUMN skipped TEXT NOT NULL DEFAULT '{}';
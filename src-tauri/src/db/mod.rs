@@ -2,4 +2,8 @@ pub mod attachments;
 pub mod chat;
 pub mod db_manager;
 pub mod embeddings;
+pub mod filter;
+pub mod ingestion_jobs;
 pub mod notebooks;
+pub mod settings;
+pub mod watched_folders;
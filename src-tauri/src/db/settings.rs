@@ -0,0 +1,179 @@
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+
+use crate::{
+    ai::{embeds::EmbeddingModelKind, providers::ProviderConfig, rerank::RerankerModelKind},
+    db::embeddings::SearchMode,
+};
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct Settings {
+    pub provider: ProviderConfig,
+    /// How many chunks are retrieved and sent to the model with each question.
+    pub top_k: usize,
+    /// Default retrieval mode when a request doesn't ask for one.
+    pub search_mode: SearchMode,
+    pub embedding_model: EmbeddingModelKind,
+    /// How many chunks are embedded at once.
+    pub batch_size: usize,
+    /// Target chunk size and overlap between chunks, in tokens.
+    pub chunk_size: usize,
+    pub chunk_overlap: usize,
+    /// What to do when the original of an imported file changes.
+    pub source_watch: SourceWatch,
+    /// Whether retrieved chunks are reranked with a cross-encoder before being used.
+    pub rerank: bool,
+    pub reranker_model: RerankerModelKind,
+    /// How many chunks are retrieved for the reranker, MMR and the per-file cap to
+    /// pick the top-k from.
+    #[serde(alias = "rerank_candidates")]
+    pub candidate_pool: usize,
+    /// Picks results with maximal marginal relevance, so they don't all say the same thing.
+    pub mmr: bool,
+    /// Weight of relevance against diversity for MMR, from 0 to 1.
+    pub mmr_lambda: f32,
+    /// Most chunks a single file can contribute to the results.
+    pub max_chunks_per_file: Option<usize>,
+    /// How many chunks before and after each result are added to it as context.
+    pub context_chunks: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SourceWatch {
+    /// Original files aren't checked.
+    Off,
+    /// Attachments are flagged as stale, and re-indexed on demand.
+    #[default]
+    Flag,
+    /// Attachments are re-indexed right away.
+    Reindex,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            provider: ProviderConfig::default(),
+            top_k: 5,
+            search_mode: SearchMode::default(),
+            embedding_model: EmbeddingModelKind::default(),
+            batch_size: 32,
+            chunk_size: 200,
+            chunk_overlap: 40,
+            source_watch: SourceWatch::default(),
+            rerank: false,
+            reranker_model: RerankerModelKind::default(),
+            candidate_pool: 50,
+            mmr: false,
+            mmr_lambda: 0.7,
+            max_chunks_per_file: None,
+            context_chunks: 0,
+        }
+    }
+}
+
+impl Settings {
+    /// Copy safe to hand to the frontend, without the API key.
+    pub fn redacted(&self) -> Self {
+        Self {
+            provider: self.provider.redacted(),
+            ..self.clone()
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if !(1..=50).contains(&self.top_k) {
+            bail!("Top-k must be between 1 and 50.");
+        }
+
+        if !(self.top_k..=200).contains(&self.candidate_pool) {
+            bail!("The candidate pool must be between top-k and 200.");
+        }
+
+        if !(0.0..=1.0).contains(&self.mmr_lambda) {
+            bail!("MMR lambda must be between 0 and 1.");
+        }
+
+        if self.max_chunks_per_file == Some(0) {
+            bail!("The chunks per file limit must be at least 1.");
+        }
+
+        if self.context_chunks > 5 {
+            bail!("Context chunks can't be more than 5.");
+        }
+
+        if !(1..=512).contains(&self.batch_size) {
+            bail!("Batch size must be between 1 and 512.");
+        }
+
+        let max_chunk_size = self.embedding_model.max_chunk_tokens();
+        if !(32..=max_chunk_size).contains(&self.chunk_size) {
+            bail!(
+                "Chunk size must be between 32 and {} tokens for this embedding model.",
+                max_chunk_size
+            );
+        }
+
+        if self.chunk_overlap * 2 > self.chunk_size {
+            bail!("Chunk overlap can't be more than half of the chunk size.");
+        }
+
+        let (url, model) = match &self.provider {
+            ProviderConfig::Ollama { host, model } => (host, model),
+            ProviderConfig::OpenAi {
+                base_url, model, ..
+            } => (base_url, model),
+        };
+
+        if model.trim().is_empty() {
+            bail!("The model name can't be empty.");
+        }
+
+        if !url.starts_with("http://") && !url.starts_with("https://") {
+            bail!("The provider URL must start with http:// or https://.");
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+pub struct SettingsRepository {
+    pool: SqlitePool,
+}
+
+impl SettingsRepository {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    /// Returns the stored settings, or the defaults if they were never saved.
+    pub async fn get(&self) -> Result<Settings> {
+        let value = sqlx::query_scalar::<_, String>("SELECT value FROM settings WHERE id = 1")
+            .fetch_optional(&self.pool)
+            .await
+            .context("Failed to load settings")?;
+
+        match value {
+            Some(value) => serde_json::from_str(&value).context("Stored settings are invalid"),
+            None => Ok(Settings::default()),
+        }
+    }
+
+    pub async fn save(&self, settings: &Settings) -> Result<()> {
+        let value = serde_json::to_string(settings).context("Failed to serialize settings")?;
+
+        sqlx::query(
+            "INSERT INTO settings (id, value) VALUES (1, ?)
+             ON CONFLICT(id) DO UPDATE SET value = excluded.value",
+        )
+        .bind(value)
+        .execute(&self.pool)
+        .await
+        .context("Failed to save settings")?;
+
+        Ok(())
+    }
+}
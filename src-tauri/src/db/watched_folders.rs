@@ -0,0 +1,129 @@
+use std::collections::HashMap;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, SqlitePool};
+use uuid::Uuid;
+
+/// A folder whose files are kept in sync with a notebook.
+#[derive(Serialize, Deserialize, Debug, Clone, FromRow)]
+pub struct WatchedFolder {
+    pub id: String,
+    pub notebook_id: String,
+    pub path: String,
+    pub created_at: i64,
+    pub last_synced_at: Option<i64>,
+    /// Why the last sync failed, cleared by the next successful one.
+    pub last_error: Option<String>,
+    /// Files of the folder that were skipped or rejected, with their modification
+    /// time then. Stored as a JSON object, they're only tried again once they change.
+    #[sqlx(json)]
+    #[serde(skip)]
+    pub skipped: HashMap<String, Option<i64>>,
+}
+
+#[derive(Clone)]
+pub struct WatchedFolderRepository {
+    pool: SqlitePool,
+}
+
+impl WatchedFolderRepository {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn create(&self, notebook_id: &str, path: &str) -> Result<WatchedFolder> {
+        let folder = WatchedFolder {
+            id: Uuid::new_v4().to_string(),
+            notebook_id: notebook_id.to_string(),
+            path: path.to_string(),
+            created_at: chrono::Utc::now().timestamp(),
+            last_synced_at: None,
+            last_error: None,
+            skipped: HashMap::new(),
+        };
+
+        sqlx::query(
+            "INSERT INTO watched_folders (id, notebook_id, path, created_at) VALUES (?, ?, ?, ?)",
+        )
+        .bind(&folder.id)
+        .bind(&folder.notebook_id)
+        .bind(&folder.path)
+        .bind(folder.created_at)
+        .execute(&self.pool)
+        .await
+        .context("Failed to watch this folder, is it already watched by this notebook?")?;
+
+        Ok(folder)
+    }
+
+    pub async fn get_by_id(&self, id: &str) -> Result<WatchedFolder> {
+        sqlx::query_as::<_, WatchedFolder>("SELECT * FROM watched_folders WHERE id = ?")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?
+            .context(format!("Watched folder with ID {} not found", id))
+    }
+
+    pub async fn get_by_notebook(&self, notebook_id: &str) -> Result<Vec<WatchedFolder>> {
+        let folders = sqlx::query_as::<_, WatchedFolder>(
+            "SELECT * FROM watched_folders WHERE notebook_id = ? ORDER BY created_at ASC",
+        )
+        .bind(notebook_id)
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to list watched folders")?;
+
+        Ok(folders)
+    }
+
+    pub async fn get_all(&self) -> Result<Vec<WatchedFolder>> {
+        let folders = sqlx::query_as::<_, WatchedFolder>(
+            "SELECT * FROM watched_folders ORDER BY created_at ASC",
+        )
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to list watched folders")?;
+
+        Ok(folders)
+    }
+
+    /// Records the outcome of a sync.
+    pub async fn update_sync(&self, id: &str, error: Option<String>) -> Result<()> {
+        sqlx::query("UPDATE watched_folders SET last_synced_at = ?, last_error = ? WHERE id = ?")
+            .bind(chrono::Utc::now().timestamp())
+            .bind(error)
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .context("Failed to update the watched folder.")?;
+        Ok(())
+    }
+
+    pub async fn update_skipped(
+        &self,
+        id: &str,
+        skipped: &HashMap<String, Option<i64>>,
+    ) -> Result<()> {
+        let skipped_json =
+            serde_json::to_string(skipped).context("Failed to serialize the skipped files")?;
+
+        sqlx::query("UPDATE watched_folders SET skipped = ? WHERE id = ?")
+            .bind(skipped_json)
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .context("Failed to update the watched folder.")?;
+        Ok(())
+    }
+
+    /// Stops watching a folder. Its files stay in the notebook.
+    pub async fn delete(&self, id: &str) -> Result<()> {
+        sqlx::query("DELETE FROM watched_folders WHERE id = ?")
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .context("Failed to stop watching this folder")?;
+        Ok(())
+    }
+}
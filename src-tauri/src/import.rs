@@ -0,0 +1,335 @@
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+use tauri::AppHandle;
+
+use crate::{
+    ai::extractors,
+    db::{
+        attachments::{Attachment, AttachmentStatus, NewAttachment},
+        ingestion_jobs::IngestionJob,
+    },
+    state::AppState,
+    store::StoredFile,
+};
+
+/// Outcome of importing several files at once.
+#[derive(Serialize, Debug, Default)]
+pub struct ImportSummary {
+    pub accepted: Vec<Attachment>,
+    /// Files that are already part of the notebook.
+    pub skipped: Vec<ImportIssue>,
+    /// Files that could not be imported.
+    pub rejected: Vec<ImportIssue>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ImportIssue {
+    pub file_path: String,
+    pub reason: String,
+}
+
+/// Every supported file under `root`, sorted by path. Hidden files and folders
+/// are left out, and symlinked folders aren't followed so loops can't happen.
+pub fn collect_folder(root: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let mut folders = vec![root.to_path_buf()];
+
+    while let Some(folder) = folders.pop() {
+        let entries = std::fs::read_dir(&folder)
+            .with_context(|| format!("Failed to read folder {}", folder.display()))?;
+
+        for entry in entries.flatten() {
+            if entry.file_name().to_string_lossy().starts_with('.') {
+                continue;
+            }
+
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+
+            if file_type.is_dir() {
+                folders.push(entry.path());
+            } else if extractors::is_supported(&entry.path()) {
+                files.push(entry.path());
+            }
+        }
+    }
+
+    files.sort();
+    Ok(files)
+}
+
+/// Copies each file into the file store and creates its attachment and ingestion
+/// job, then queues them.
+pub async fn import_files(
+    app: &AppHandle,
+    state: &AppState,
+    notebook_id: &str,
+    paths: Vec<PathBuf>,
+    watched_folder_id: Option<&str>,
+) -> Result<ImportSummary> {
+    let mut summary = ImportSummary::default();
+
+    // Files that failed or were cancelled can be imported again.
+    let mut imported: HashSet<String> = state
+        .db
+        .get_attachments_repository()
+        .get_by_notebook(notebook_id)
+        .await?
+        .iter()
+        .filter(|a| matches!(a.status.as_str(), "pending" | "ready"))
+        .map(|a| a.source_path().to_string())
+        .collect();
+
+    for path in paths {
+        let file_path = path.to_string_lossy().to_string();
+
+        if imported.contains(&file_path) {
+            summary.skipped.push(ImportIssue {
+                file_path,
+                reason: "Skipped on purpose: this file is already in this notebook.".to_string(),
+            });
+            continue;
+        }
+
+        if !extractors::is_supported(&path) {
+            summary.rejected.push(ImportIssue {
+                file_path,
+                reason: "Unsupported file format.".to_string(),
+            });
+            continue;
+        }
+
+        match import_file(app, state, notebook_id, &path, watched_folder_id).await {
+            Ok(Imported::New(attachment)) => {
+                imported.insert(file_path);
+                summary.accepted.push(attachment);
+            }
+            Ok(Imported::Duplicate(existing)) => summary.skipped.push(ImportIssue {
+                file_path,
+                reason: format!(
+                    "Skipped on purpose: same content as {}, which is already in this notebook.",
+                    existing.file_name
+                ),
+            }),
+            Err(e) => summary.rejected.push(ImportIssue {
+                file_path,
+                reason: e.to_string(),
+            }),
+        }
+    }
+
+    Ok(summary)
+}
+
+enum Imported {
+    New(Attachment),
+    /// The notebook already has a file with the same content.
+    Duplicate(Attachment),
+}
+
+async fn import_file(
+    app: &AppHandle,
+    state: &AppState,
+    notebook_id: &str,
+    path: &Path,
+    watched_folder_id: Option<&str>,
+) -> Result<Imported> {
+    let file_name = path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .context("File not found.")?
+        .to_string();
+
+    let metadata = std::fs::metadata(path).context("Failed to read file metadata.")?;
+
+    let size = metadata.len() as i64;
+    let mime = path
+        .extension()
+        .and_then(|s| s.to_str())
+        .unwrap_or("bin")
+        .to_string();
+
+    let stored = copy_to_store(state, path).await?;
+    let stored_path = stored.path.to_string_lossy().to_string();
+    let attachments = state.db.get_attachments_repository();
+
+    if let Some(existing) = attachments.find_by_hash(notebook_id, &stored.hash).await? {
+        state.files.release(attachments, &stored_path).await.ok();
+        return Ok(Imported::Duplicate(existing));
+    }
+
+    let new = NewAttachment {
+        notebook_id: notebook_id.to_string(),
+        file_name,
+        file_path: stored_path.clone(),
+        original_path: path.to_string_lossy().to_string(),
+        content_hash: stored.hash,
+        source_modified_at: modified_at(path),
+        watched_folder_id: watched_folder_id.map(str::to_string),
+        file_size: size,
+        file_type: mime,
+    };
+
+    let (attachment, job) = match create_records(state, new).await {
+        Ok(created) => created,
+        Err(e) => {
+            // Don't leave a copy nothing points at.
+            state.files.release(attachments, &stored_path).await.ok();
+            return Err(e);
+        }
+    };
+
+    // A new attachment can't have a job already.
+    state.ingestion.try_enqueue(app, job);
+
+    Ok(Imported::New(attachment))
+}
+
+async fn create_records(
+    state: &AppState,
+    new: NewAttachment,
+) -> Result<(Attachment, IngestionJob)> {
+    let notebook_id = new.notebook_id.clone();
+    let mut tx = state.db.begin_transaction().await?;
+
+    let attachment = state
+        .db
+        .get_attachments_repository()
+        .create_with_tx(&mut tx, new)
+        .await?;
+
+    // The job is created with the attachment, so it can be resumed if the app
+    // closes before it's processed.
+    let job = state
+        .db
+        .get_ingestion_jobs_repository()
+        .create_with_tx(&mut tx, &attachment.id, &notebook_id)
+        .await?;
+
+    tx.commit()
+        .await
+        .context("Failed to commit database transaction")?;
+
+    Ok((attachment, job))
+}
+
+/// Indexes an attachment again. If its original file is still there, it's copied
+/// into the store again first so changes to it are picked up.
+pub async fn reindex(
+    app: &AppHandle,
+    state: &AppState,
+    attachment: Attachment,
+) -> Result<Attachment> {
+    // Claimed before anything changes, so a concurrent re-index or sync can't
+    // queue a second job for it.
+    let Some(reservation) = state.ingestion.try_reserve(&attachment.id) else {
+        bail!("This file is already being processed.");
+    };
+
+    let attachments = state.db.get_attachments_repository();
+    let original = PathBuf::from(attachment.source_path());
+
+    if original.is_file() {
+        let stored = copy_to_store(state, &original).await?;
+        let stored_path = stored.path.to_string_lossy().to_string();
+        let size = std::fs::metadata(&original)
+            .context("Failed to read file metadata.")?
+            .len() as i64;
+
+        attachments
+            .update_source(
+                &attachment.id,
+                &stored_path,
+                attachment.source_path(),
+                &stored.hash,
+                size,
+                modified_at(&original),
+            )
+            .await?;
+
+        if stored_path != attachment.file_path {
+            state
+                .files
+                .release(attachments, &attachment.file_path)
+                .await
+                .ok();
+        }
+    } else {
+        // The original is gone, the stored copy is indexed again as it is.
+        attachments
+            .update_status(&attachment.id, AttachmentStatus::Pending)
+            .await?;
+    }
+
+    let job = state
+        .db
+        .get_ingestion_jobs_repository()
+        .create(&attachment.id, &attachment.notebook_id)
+        .await?;
+    reservation.enqueue(app, job);
+
+    attachments.get_by_id(&attachment.id).await
+}
+
+/// Deletes an attachment with its embeddings and, if nothing else uses it, its
+/// stored copy.
+pub async fn remove_attachment(state: &AppState, attachment: Attachment) -> Result<()> {
+    // Stop a job that may still be working on this file.
+    state.ingestion.cancel(&attachment.id);
+
+    let mut tx = state.db.begin_transaction().await?;
+
+    state
+        .db
+        .get_attachments_repository()
+        .delete_with_tx(&mut tx, &attachment.id)
+        .await?;
+
+    state
+        .db
+        .get_embeddings_repository()
+        .remove_document_embeddings(&attachment.id)
+        .await?;
+
+    tx.commit()
+        .await
+        .context("Failed to commit database transaction")?;
+
+    release_stored_files(state, vec![attachment]).await;
+
+    Ok(())
+}
+
+/// Deletes the stored copies that deleted attachments leave unused. The attachments
+/// are already gone, so failing here only leaves a file behind.
+pub async fn release_stored_files(state: &AppState, attachments: Vec<Attachment>) {
+    let repository = state.db.get_attachments_repository();
+
+    for attachment in attachments {
+        if let Err(e) = state.files.release(repository, &attachment.file_path).await {
+            eprintln!("Could not delete {}: {}", attachment.file_path, e);
+        }
+    }
+}
+
+/// Last modification time of a file, in seconds.
+pub fn modified_at(path: &Path) -> Option<i64> {
+    let modified = std::fs::metadata(path).ok()?.modified().ok()?;
+    let since_epoch = modified.duration_since(std::time::UNIX_EPOCH).ok()?;
+    Some(since_epoch.as_secs() as i64)
+}
+
+async fn copy_to_store(state: &AppState, path: &Path) -> Result<StoredFile> {
+    let store = state.files.clone();
+    let source = path.to_path_buf();
+
+    tauri::async_runtime::spawn_blocking(move || store.put(&source))
+        .await
+        .context("Failed to copy the file into the file store.")?
+}
@@ -0,0 +1,496 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager};
+use tokio::sync::Semaphore;
+
+use crate::{
+    ai::extractors,
+    db::{
+        attachments::{Attachment, AttachmentStatus},
+        ingestion_jobs::{IngestionJob, IngestionJobStatus},
+    },
+    state::AppState,
+};
+
+/// How many files are processed at the same time.
+const MAX_CONCURRENT_JOBS: usize = 2;
+
+/// Attempts before a job that keeps failing is marked as failed.
+const MAX_ATTEMPTS: i64 = 3;
+
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum IngestionStage {
+    Queued,
+    Extracted,
+    Chunked,
+    Embedding,
+    Storing,
+    Retrying,
+}
+
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct IngestionProgress {
+    pub job_id: String,
+    pub attachment_id: String,
+    pub stage: IngestionStage,
+    /// Embedded chunks so far, only meaningful while embedding.
+    pub done: usize,
+    pub total: usize,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct ProcessingError {
+    pub id: String,
+    pub reason: String,
+}
+
+enum JobFailure {
+    Cancelled,
+    /// Retrying won't help, e.g. the file can't be parsed.
+    Permanent(anyhow::Error),
+    Transient(anyhow::Error),
+}
+
+/// An attachment claimed by `IngestionQueue::try_reserve`. Dropping it without
+/// queuing a job gives the attachment back.
+pub struct Reservation {
+    queue: IngestionQueue,
+    attachment_id: String,
+    // Taken by the queued job, which then holds the claim until it finishes.
+    cancelled: Option<Arc<AtomicBool>>,
+}
+
+impl Reservation {
+    pub fn enqueue(mut self, app: &AppHandle, job: IngestionJob) {
+        if let Some(cancelled) = self.cancelled.take() {
+            self.queue.spawn(app, job, cancelled);
+        }
+    }
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        if let Some(cancelled) = &self.cancelled {
+            self.queue.release(&self.attachment_id, cancelled);
+        }
+    }
+}
+
+/// Runs ingestion jobs in the background, a few at a time.
+///
+/// Jobs are stored in the `ingestion_jobs` table before being queued, so the ones
+/// that didn't finish can be picked up again by `resume` when the app restarts.
+#[derive(Clone)]
+pub struct IngestionQueue {
+    permits: Arc<Semaphore>,
+    // Cancellation flags of the jobs that are queued or running, by attachment id.
+    cancellations: Arc<std::sync::Mutex<HashMap<String, Arc<AtomicBool>>>>,
+}
+
+impl IngestionQueue {
+    pub fn new() -> Self {
+        Self {
+            permits: Arc::new(Semaphore::new(MAX_CONCURRENT_JOBS)),
+            cancellations: Arc::new(std::sync::Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Queues a job, unless its attachment already has one queued or running.
+    pub fn try_enqueue(&self, app: &AppHandle, job: IngestionJob) -> bool {
+        match self.try_reserve(&job.attachment_id) {
+            Some(reservation) => {
+                reservation.enqueue(app, job);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Claims an attachment for a job that is about to be created. Checking and
+    /// claiming happen under the same lock, so only one caller gets it.
+    pub fn try_reserve(&self, attachment_id: &str) -> Option<Reservation> {
+        let mut cancellations = self.cancellations.lock().unwrap();
+        if cancellations.contains_key(attachment_id) {
+            return None;
+        }
+
+        let cancelled = Arc::new(AtomicBool::new(false));
+        cancellations.insert(attachment_id.to_string(), cancelled.clone());
+
+        Some(Reservation {
+            queue: self.clone(),
+            attachment_id: attachment_id.to_string(),
+            cancelled: Some(cancelled),
+        })
+    }
+
+    fn spawn(&self, app: &AppHandle, job: IngestionJob, cancelled: Arc<AtomicBool>) {
+        emit_progress(app, &job, IngestionStage::Queued, 0, 0);
+
+        let queue = self.clone();
+        let app = app.clone();
+        tauri::async_runtime::spawn(async move {
+            let Ok(_permit) = queue.permits.clone().acquire_owned().await else {
+                return;
+            };
+
+            run_job(&app, &job, &cancelled).await;
+            queue.release(&job.attachment_id, &cancelled);
+        });
+    }
+
+    /// Forgets the cancellation flag of a finished job, unless it was already
+    /// replaced by the flag of a newer job of the same attachment.
+    fn release(&self, attachment_id: &str, cancelled: &Arc<AtomicBool>) {
+        let mut cancellations = self.cancellations.lock().unwrap();
+        if cancellations
+            .get(attachment_id)
+            .is_some_and(|flag| Arc::ptr_eq(flag, cancelled))
+        {
+            cancellations.remove(attachment_id);
+        }
+    }
+
+    /// Asks the job of an attachment to stop. Returns false if it isn't queued or running.
+    pub fn cancel(&self, attachment_id: &str) -> bool {
+        match self.cancellations.lock().unwrap().get(attachment_id) {
+            Some(flag) => {
+                flag.store(true, Ordering::SeqCst);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Whether the attachment has a job queued or running.
+    pub fn is_active(&self, attachment_id: &str) -> bool {
+        self.cancellations
+            .lock()
+            .unwrap()
+            .contains_key(attachment_id)
+    }
+
+    /// Queues again every attachment that was still pending when the app was closed.
+    pub async fn resume(&self, app: &AppHandle) -> Result<()> {
+        let state = app.state::<AppState>();
+        let jobs = state.db.get_ingestion_jobs_repository();
+
+        for attachment in state.db.get_attachments_repository().get_pending().await? {
+            let job = match jobs.get_latest_by_attachment(&attachment.id).await? {
+                Some(job) if job.status == "queued" || job.status == "running" => job,
+                // Attachments uploaded before the queue existed have no job.
+                _ => jobs.create(&attachment.id, &attachment.notebook_id).await?,
+            };
+
+            self.try_enqueue(app, job);
+        }
+
+        Ok(())
+    }
+}
+
+async fn run_job(app: &AppHandle, job: &IngestionJob, cancelled: &AtomicBool) {
+    let state = app.state::<AppState>();
+    let jobs = state.db.get_ingestion_jobs_repository();
+    let attachments = state.db.get_attachments_repository();
+    let mut attempts = job.attempts;
+
+    loop {
+        if cancelled.load(Ordering::SeqCst) {
+            finish_cancelled(app, job).await;
+            return;
+        }
+
+        attempts += 1;
+        jobs.start_attempt(&job.id).await.ok();
+        app.emit("processing-start", &job.attachment_id).ok();
+
+        let failure = match process(app, job, cancelled).await {
+            Ok(()) => {
+                jobs.update_status(&job.id, IngestionJobStatus::Done, None)
+                    .await
+                    .ok();
+                attachments
+                    .update_status(&job.attachment_id, AttachmentStatus::Ready)
+                    .await
+                    .ok();
+                app.emit("processing-success", &job.attachment_id).ok();
+                return;
+            }
+            Err(failure) => failure,
+        };
+
+        let error = match failure {
+            JobFailure::Cancelled => {
+                finish_cancelled(app, job).await;
+                return;
+            }
+            JobFailure::Transient(e) if attempts < MAX_ATTEMPTS => {
+                eprintln!("Job {} failed, retrying: {}", job.id, e);
+                emit_progress(app, job, IngestionStage::Retrying, 0, 0);
+
+                // Back off a bit more after every failed attempt.
+                tokio::time::sleep(Duration::from_secs(2u64.pow(attempts as u32))).await;
+                continue;
+            }
+            JobFailure::Transient(e) | JobFailure::Permanent(e) => e,
+        };
+
+        eprintln!("Job {} failed: {}", job.id, error);
+        jobs.update_status(&job.id, IngestionJobStatus::Failed, Some(error.to_string()))
+            .await
+            .ok();
+        attachments
+            .update_status(&job.attachment_id, AttachmentStatus::Error)
+            .await
+            .ok();
+        app.emit(
+            "processing-error",
+            ProcessingError {
+                id: job.attachment_id.clone(),
+                reason: error.to_string(),
+            },
+        )
+        .ok();
+        return;
+    }
+}
+
+async fn finish_cancelled(app: &AppHandle, job: &IngestionJob) {
+    let state = app.state::<AppState>();
+
+    state
+        .db
+        .get_ingestion_jobs_repository()
+        .update_status(&job.id, IngestionJobStatus::Cancelled, None)
+        .await
+        .ok();
+    state
+        .db
+        .get_attachments_repository()
+        .update_status(&job.attachment_id, AttachmentStatus::Cancelled)
+        .await
+        .ok();
+    app.emit(
+        "processing-error",
+        ProcessingError {
+            id: job.attachment_id.clone(),
+            reason: "Processing was cancelled.".to_string(),
+        },
+    )
+    .ok();
+}
+
+async fn process(
+    app: &AppHandle,
+    job: &IngestionJob,
+    cancelled: &AtomicBool,
+) -> Result<(), JobFailure> {
+    let state = app.state::<AppState>();
+    let check_cancelled = || {
+        if cancelled.load(Ordering::SeqCst) {
+            Err(JobFailure::Cancelled)
+        } else {
+            Ok(())
+        }
+    };
+
+    let attachment = state
+        .db
+        .get_attachments_repository()
+        .get_by_id(&job.attachment_id)
+        .await
+        .map_err(JobFailure::Permanent)?;
+
+    check_cancelled()?;
+    let reused = reuse_embeddings(&state, &attachment)
+        .await
+        .map_err(JobFailure::Transient)?;
+    if reused > 0 {
+        discard_if_removed(&state, job, cancelled).await?;
+        emit_progress(app, job, IngestionStage::Storing, reused, reused);
+        return Ok(());
+    }
+
+    let path = attachment.file_path.clone();
+    let document = tauri::async_runtime::spawn_blocking(move || extractors::extract_content(&path))
+        .await
+        .map_err(|e| JobFailure::Transient(anyhow!(e)))?
+        .map_err(JobFailure::Permanent)?;
+
+    if document.metadata != attachment.metadata {
+        state
+            .db
+            .get_attachments_repository()
+            .update_metadata(&job.attachment_id, &document.metadata)
+            .await
+            .map_err(JobFailure::Transient)?;
+    }
+
+    emit_progress(app, job, IngestionStage::Extracted, 0, 0);
+    check_cancelled()?;
+
+    let (chunks, batch_size) = {
+        let model = state.embeddings_model.lock().await;
+        (model.chunk(&document), model.batch_size())
+    };
+
+    if chunks.is_empty() {
+        return Err(JobFailure::Permanent(anyhow!(
+            "No text could be extracted from {}",
+            attachment.file_name
+        )));
+    }
+
+    let total = chunks.len();
+    emit_progress(app, job, IngestionStage::Chunked, 0, total);
+
+    // The model is locked one batch at a time, so chat queries and other jobs
+    // can use it in between.
+    let mut embeddings = Vec::with_capacity(total);
+    for batch in chunks.chunks(batch_size) {
+        check_cancelled()?;
+
+        let texts: Vec<&str> = batch.iter().map(|c| c.text.as_str()).collect();
+        let vectors = state
+            .embeddings_model
+            .lock()
+            .await
+            .embed_batch(&texts)
+            .map_err(JobFailure::Transient)?;
+
+        embeddings.extend(vectors);
+        emit_progress(app, job, IngestionStage::Embedding, embeddings.len(), total);
+    }
+
+    check_cancelled()?;
+    emit_progress(app, job, IngestionStage::Storing, total, total);
+
+    let record_batch = state
+        .embeddings_model
+        .lock()
+        .await
+        .build_record_batch(
+            attachment.source_path(),
+            &job.notebook_id,
+            &job.attachment_id,
+            &chunks,
+            embeddings,
+        )
+        .map_err(JobFailure::Permanent)?;
+
+    let repository = state.db.get_embeddings_repository();
+
+    // A previous attempt may have stored rows before failing.
+    repository
+        .remove_document_embeddings(&job.attachment_id)
+        .await
+        .map_err(JobFailure::Transient)?;
+    repository
+        .add_document(record_batch)
+        .await
+        .map_err(JobFailure::Transient)?;
+
+    discard_if_removed(&state, job, cancelled).await
+}
+
+/// Deletes the rows a job just stored if it was cancelled or its attachment removed
+/// meanwhile. Removing an attachment cancels its job before deleting its rows, so a
+/// job that stored them after that is the one left to clean up.
+async fn discard_if_removed(
+    state: &AppState,
+    job: &IngestionJob,
+    cancelled: &AtomicBool,
+) -> Result<(), JobFailure> {
+    let removed = cancelled.load(Ordering::SeqCst)
+        || !state
+            .db
+            .get_attachments_repository()
+            .exists(&job.attachment_id)
+            .await
+            .map_err(JobFailure::Transient)?;
+
+    if !removed {
+        return Ok(());
+    }
+
+    state
+        .db
+        .get_embeddings_repository()
+        .remove_document_embeddings(&job.attachment_id)
+        .await
+        .map_err(JobFailure::Transient)?;
+
+    Err(JobFailure::Cancelled)
+}
+
+/// Copies the embeddings of an indexed attachment with the same content, from
+/// this notebook or another one, instead of embedding the file again. Returns how
+/// many chunks were copied.
+async fn reuse_embeddings(state: &AppState, attachment: &Attachment) -> Result<usize> {
+    let Some(content_hash) = &attachment.content_hash else {
+        return Ok(0);
+    };
+
+    let attachments = state.db.get_attachments_repository();
+    let Some(source) = attachments
+        .find_ready_by_hash(content_hash, &attachment.id)
+        .await?
+    else {
+        return Ok(0);
+    };
+
+    let repository = state.db.get_embeddings_repository();
+
+    // A previous attempt may have stored rows before failing.
+    repository
+        .remove_document_embeddings(&attachment.id)
+        .await?;
+    let copied = repository
+        .copy_document(
+            &source.id,
+            &attachment.id,
+            &attachment.notebook_id,
+            attachment.source_path(),
+        )
+        .await?;
+
+    if copied > 0 && source.metadata != attachment.metadata {
+        attachments
+            .update_metadata(&attachment.id, &source.metadata)
+            .await?;
+    }
+
+    Ok(copied)
+}
+
+fn emit_progress(
+    app: &AppHandle,
+    job: &IngestionJob,
+    stage: IngestionStage,
+    done: usize,
+    total: usize,
+) {
+    app.emit(
+        "ingestion-progress",
+        IngestionProgress {
+            job_id: job.id.clone(),
+            attachment_id: job.attachment_id.clone(),
+            stage,
+            done,
+            total,
+        },
+    )
+    .ok();
+}
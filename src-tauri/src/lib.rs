@@ -4,16 +4,23 @@ use tauri::async_runtime::Mutex;
 use tauri::Manager;
 
 use crate::{
-    ai::{embeds::EmbedModel, llama::Model},
+    ai::{embeds::EmbedModel, llama::Model, rerank::Reranker},
     commands::register_commands,
     db::db_manager::DBManager,
+    ingestion::IngestionQueue,
     state::AppState,
+    store::FileStore,
 };
 
 mod ai;
 mod commands;
 mod db;
+mod import;
+mod ingestion;
+mod retrieval;
 mod state;
+mod store;
+mod watcher;
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
@@ -50,24 +57,41 @@ pub fn run() {
                         .expect("Failed to initialize DBManager"),
                 );
 
+                let files = FileStore::new(app_dir.join("files"))
+                    .expect("Could not create the file store.");
+
+                let settings = db_manager
+                    .get_settings_repository()
+                    .get()
+                    .await
+                    .expect("Could not load the settings.");
+
+                let reranker = Arc::new(std::sync::Mutex::new(Reranker::new(app_dir.clone())));
+
                 let model = Arc::new(Mutex::new(
-                    EmbedModel::new(app_dir).expect("Could not create the embed model."),
+                    EmbedModel::new(app_dir, &settings).expect("Could not create the embed model."),
                 ));
 
-                let chat_model = Arc::new(Mutex::new(Model::new("phi4-mini")));
-
-                chat_model
-                    .lock()
-                    .await
-                    .inject_system_prompt()
-                    .await
-                    .unwrap();
+                let chat_model = Arc::new(Mutex::new(
+                    Model::new(&settings.provider).expect("Could not create the chat model."),
+                ));
 
                 handle.manage(AppState {
                     db: db_manager,
                     embeddings_model: model,
+                    reranker,
                     chat_model: chat_model,
+                    settings: Arc::new(Mutex::new(settings)),
+                    ingestion: IngestionQueue::new(),
+                    files,
                 });
+
+                let state = handle.state::<AppState>();
+                if let Err(e) = state.ingestion.resume(&handle).await {
+                    eprintln!("Could not resume pending ingestion jobs: {}", e);
+                }
+
+                watcher::spawn(handle.clone());
             });
             Ok(())
         })
@@ -0,0 +1,774 @@
+use std::collections::HashMap;
+
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+
+use crate::{
+    db::{
+        embeddings::{SearchMode, VectorSearchResult},
+        settings::Settings,
+    },
+    state::AppState,
+};
+
+/// How many characters of a chunk are shown around its best match.
+const SNIPPET_CHARS: usize = 240;
+
+/// Per-request knobs for retrieval. Anything left empty falls back to the settings.
+#[derive(Debug, Clone, Default)]
+pub struct RetrievalOptions {
+    pub top_k: Option<usize>,
+    pub search_mode: Option<SearchMode>,
+    /// Only search these attachments of the notebook.
+    pub attachment_ids: Option<Vec<String>>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct SearchHit {
+    #[serde(flatten)]
+    pub result: VectorSearchResult,
+    pub file_name: String,
+    pub snippet: String,
+    pub highlights: Vec<Highlight>,
+}
+
+/// Character range of `SearchHit::snippet` that matches a query term.
+#[derive(Serialize, Debug, Clone, Copy)]
+pub struct Highlight {
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Finds the chunks of a notebook that best match `query`. With reranking on, more
+/// candidates are retrieved and a cross-encoder picks the top-k among them.
+pub async fn retrieve(
+    state: &AppState,
+    notebook_id: &str,
+    query: &str,
+    options: &RetrievalOptions,
+) -> Result<Vec<VectorSearchResult>> {
+    let settings = state.settings.lock().await.clone();
+
+    let top_k = options.top_k.unwrap_or(settings.top_k);
+    let attachment_ids = options.attachment_ids.as_deref();
+    if let Some(ids) = attachment_ids {
+        // Otherwise a selection of deleted files would look like nothing matched.
+        let in_notebook = state
+            .db
+            .get_attachments_repository()
+            .get_by_notebook(notebook_id)
+            .await?;
+        if !in_notebook.iter().any(|a| ids.contains(&a.id)) {
+            bail!("None of the selected files are in this notebook.");
+        }
+    }
+
+    let limit = if settings.rerank || settings.mmr || settings.max_chunks_per_file.is_some() {
+        settings.candidate_pool.max(top_k)
+    } else {
+        top_k
+    };
+
+    let query_vector = {
+        let mut model = state.embeddings_model.lock().await;
+        model.generate_from_text(query).await?
+    };
+
+    let repository = state.db.get_embeddings_repository();
+    let mut results = match options.search_mode.unwrap_or(settings.search_mode) {
+        SearchMode::Vector => {
+            repository
+                .search(notebook_id, attachment_ids, query_vector, limit)
+                .await?
+        }
+        SearchMode::Hybrid => {
+            repository
+                .hybrid_search(notebook_id, attachment_ids, query, query_vector, limit)
+                .await?
+        }
+    };
+
+    if settings.rerank && results.len() > 1 {
+        rerank(state, &settings, query, &mut results).await?;
+    }
+
+    let lambda = settings.mmr.then_some(settings.mmr_lambda);
+    let results = select(results, top_k, lambda, settings.max_chunks_per_file);
+
+    if settings.context_chunks > 0 {
+        return expand(state, results, settings.context_chunks as u32).await;
+    }
+
+    Ok(results)
+}
+
+/// A result and the chunks of its file it's expanded to.
+struct Window {
+    result: VectorSearchResult,
+    range: Option<(u32, u32)>,
+}
+
+impl Window {
+    /// The result with the `n` chunks before and after it.
+    fn new(result: VectorSearchResult, n: u32) -> Self {
+        let range = result
+            .chunk_index
+            .map(|index| (index.saturating_sub(n), index + n));
+        Self { result, range }
+    }
+}
+
+/// Adds the `n` chunks before and after each result, from the same file, to its
+/// text. Results whose windows overlap are merged into the one ranked first.
+async fn expand(
+    state: &AppState,
+    results: Vec<VectorSearchResult>,
+    n: u32,
+) -> Result<Vec<VectorSearchResult>> {
+    let mut windows: Vec<Window> = Vec::new();
+
+    for result in results {
+        windows.push(Window::new(result, n));
+        merge_windows(&mut windows);
+    }
+
+    let repository = state.db.get_embeddings_repository();
+    let mut expanded = Vec::with_capacity(windows.len());
+
+    for mut window in windows {
+        if let Some((first, last)) = window.range {
+            let chunks = repository
+                .get_chunks(&window.result.attachment_id, first, last)
+                .await?;
+
+            if !chunks.is_empty() {
+                window.result.text = join_chunks(&chunks);
+                widen_location(&mut window.result, &chunks);
+            }
+        }
+
+        expanded.push(window.result);
+    }
+
+    Ok(expanded)
+}
+
+/// Merges the last window into an earlier one of the same file it overlaps or
+/// touches, then does the same for the grown window.
+fn merge_windows(windows: &mut Vec<Window>) {
+    let mut i = windows.len() - 1;
+
+    while let Some((first, last)) = windows[i].range {
+        let target = (0..windows.len()).find(|&j| {
+            j != i
+                && windows[j].result.attachment_id == windows[i].result.attachment_id
+                && windows[j]
+                    .range
+                    .is_some_and(|(f, l)| first <= l + 1 && f <= last + 1)
+        });
+
+        let Some(j) = target else {
+            break;
+        };
+
+        let merged = windows.remove(i.max(j));
+        let kept = &mut windows[i.min(j)];
+        let (f, l) = kept.range.unwrap_or((first, last));
+        let (mf, ml) = merged.range.unwrap_or((first, last));
+        kept.range = Some((f.min(mf), l.max(ml)));
+        kept.result.score = kept.result.score.max(merged.result.score);
+        i = i.min(j);
+    }
+}
+
+/// Makes the location of an expanded result cover all of its chunks. A page or
+/// section they don't all share no longer applies, nor do rows of another section.
+fn widen_location(result: &mut VectorSearchResult, chunks: &[VectorSearchResult]) {
+    if chunks.iter().all(|c| c.section == result.section) {
+        result.row_start = chunks.iter().filter_map(|c| c.row_start).min();
+        result.row_end = chunks.iter().filter_map(|c| c.row_end).max();
+    } else {
+        result.section = None;
+        result.row_start = None;
+        result.row_end = None;
+    }
+
+    if !chunks.iter().all(|c| c.page == result.page) {
+        result.page = None;
+    }
+
+    result.line_start = chunks.iter().filter_map(|c| c.line_start).min();
+    result.line_end = chunks.iter().filter_map(|c| c.line_end).max();
+    result.start_offset = chunks.iter().filter_map(|c| c.start_offset).min();
+    result.end_offset = chunks.iter().filter_map(|c| c.end_offset).max();
+}
+
+/// Text of consecutive chunks, without the overlap between them.
+fn join_chunks(chunks: &[VectorSearchResult]) -> String {
+    let mut text = String::new();
+    let mut covered_until: Option<u32> = None;
+
+    for chunk in chunks {
+        match (covered_until, chunk.start_offset, chunk.end_offset) {
+            (Some(covered), Some(start), Some(end)) if start < covered => {
+                if end <= covered {
+                    continue;
+                }
+                text.extend(chunk.text.chars().skip((covered - start) as usize));
+            }
+            (Some(_), _, _) => {
+                text.push('\n');
+                text.push_str(&chunk.text);
+            }
+            (None, _, _) => text.push_str(&chunk.text),
+        }
+
+        covered_until = chunk.end_offset.max(covered_until);
+    }
+
+    text
+}
+
+/// Picks `top_k` results out of candidates sorted by relevance, skipping files that
+/// already have `max_per_file` results. With a `lambda`, each pick maximises
+/// `lambda * relevance - (1 - lambda) * similarity to the results already picked`.
+fn select(
+    mut candidates: Vec<VectorSearchResult>,
+    top_k: usize,
+    lambda: Option<f32>,
+    max_per_file: Option<usize>,
+) -> Vec<VectorSearchResult> {
+    // Scores from the different search modes have different scales.
+    let (min, max) = candidates
+        .iter()
+        .fold((f32::MAX, f32::MIN), |(min, max), c| {
+            (min.min(c.score), max.max(c.score))
+        });
+    let relevance = |c: &VectorSearchResult| {
+        if max > min {
+            (c.score - min) / (max - min)
+        } else {
+            1.0
+        }
+    };
+
+    let mut selected: Vec<VectorSearchResult> = Vec::new();
+    let mut per_file: HashMap<String, usize> = HashMap::new();
+
+    while selected.len() < top_k {
+        candidates.retain(|c| {
+            max_per_file
+                .is_none_or(|max| per_file.get(&c.attachment_id).copied().unwrap_or(0) < max)
+        });
+
+        let best = match lambda {
+            // Candidates are sorted, the first one is the most relevant.
+            None => 0,
+            Some(lambda) => {
+                let marginal_relevance = |c: &VectorSearchResult| {
+                    let redundancy = selected
+                        .iter()
+                        .map(|s| cosine_similarity(&c.vector, &s.vector))
+                        .fold(0.0, f32::max);
+                    lambda * relevance(c) - (1.0 - lambda) * redundancy
+                };
+
+                // On a tie the first, more relevant, candidate wins.
+                (0..candidates.len())
+                    .max_by(|&a, &b| {
+                        marginal_relevance(&candidates[a])
+                            .total_cmp(&marginal_relevance(&candidates[b]))
+                            .then(b.cmp(&a))
+                    })
+                    .unwrap_or(0)
+            }
+        };
+
+        if best >= candidates.len() {
+            break;
+        }
+
+        let pick = candidates.remove(best);
+        *per_file.entry(pick.attachment_id.clone()).or_insert(0) += 1;
+        selected.push(pick);
+    }
+
+    selected
+}
+
+/// Cosine similarity of two vectors, 0 if either is missing.
+fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    if a.is_empty() || a.len() != b.len() {
+        return 0.0;
+    }
+
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+
+    if norm_a == 0.0 || norm_b == 0.0 {
+        0.0
+    } else {
+        dot / (norm_a * norm_b)
+    }
+}
+
+/// Sorts the results by cross-encoder score, which replaces their retrieval score.
+async fn rerank(
+    state: &AppState,
+    settings: &Settings,
+    query: &str,
+    results: &mut [VectorSearchResult],
+) -> Result<()> {
+    let reranker = state.reranker.clone();
+    let kind = settings.reranker_model;
+    let query = query.to_string();
+    let passages: Vec<String> = results.iter().map(|r| r.text.clone()).collect();
+
+    // Loading and running the model takes a while and would stall the async runtime.
+    let scores = tauri::async_runtime::spawn_blocking(move || {
+        let passages: Vec<&str> = passages.iter().map(String::as_str).collect();
+        // A panic while scoring leaves the model as it was, so the lock is still usable.
+        let mut reranker = reranker.lock().unwrap_or_else(|e| e.into_inner());
+        reranker.score(kind, &query, &passages)
+    })
+    .await
+    .context("Failed to run the reranker.")??;
+
+    for (result, score) in results.iter_mut().zip(scores) {
+        result.score = score;
+    }
+    results.sort_by(|a, b| b.score.total_cmp(&a.score));
+
+    Ok(())
+}
+
+/// Same as `retrieve`, with the file name and a highlighted snippet for each result.
+pub async fn search(
+    state: &AppState,
+    notebook_id: &str,
+    query: &str,
+    options: &RetrievalOptions,
+) -> Result<Vec<SearchHit>> {
+    let results = retrieve(state, notebook_id, query, options).await?;
+
+    let file_names: HashMap<String, String> = state
+        .db
+        .get_attachments_repository()
+        .get_by_notebook(notebook_id)
+        .await?
+        .into_iter()
+        .map(|a| (a.id, a.file_name))
+        .collect();
+
+    let terms = query_terms(query);
+
+    Ok(results
+        .into_iter()
+        .map(|result| {
+            let (snippet, highlights) = snippet(&result.text, &terms);
+            let file_name = file_names
+                .get(&result.attachment_id)
+                .cloned()
+                .unwrap_or_else(|| result.file_path.clone());
+
+            SearchHit {
+                result,
+                file_name,
+                snippet,
+                highlights,
+            }
+        })
+        .collect())
+}
+
+/// Lowercased words of the query worth highlighting. Very short words are
+/// skipped, unless they are numbers.
+fn query_terms(query: &str) -> Vec<Vec<char>> {
+    let mut terms: Vec<Vec<char>> = Vec::new();
+
+    for word in query.split(|c: char| !c.is_alphanumeric()) {
+        let is_number = word.chars().all(|c| c.is_ascii_digit());
+        if word.is_empty() || (word.chars().count() < 3 && !is_number) {
+            continue;
+        }
+
+        let term: Vec<char> = word.chars().map(lowercase).collect();
+        if !terms.contains(&term) {
+            terms.push(term);
+        }
+    }
+
+    terms
+}
+
+/// Cuts the window of `text` with the most query matches and returns it with
+/// the positions of those matches.
+fn snippet(text: &str, terms: &[Vec<char>]) -> (String, Vec<Highlight>) {
+    let chars: Vec<char> = text.chars().collect();
+    let lower: Vec<char> = chars.iter().copied().map(lowercase).collect();
+    let matches = find_matches(&lower, terms);
+
+    // Start a bit before the densest group of matches, so they have some context.
+    let window_start = matches
+        .iter()
+        .max_by_key(|m| {
+            matches
+                .iter()
+                .filter(|other| other.start >= m.start && other.end <= m.start + SNIPPET_CHARS)
+                .count()
+        })
+        .map(|m| m.start.saturating_sub(SNIPPET_CHARS / 4))
+        .unwrap_or(0);
+
+    let mut start = window_start;
+    while start > 0 && !chars[start - 1].is_whitespace() {
+        start -= 1;
+    }
+
+    let mut end = (start + SNIPPET_CHARS).min(chars.len());
+    while end < chars.len() && !chars[end].is_whitespace() {
+        end += 1;
+    }
+
+    let prefix = if start > 0 { "…" } else { "" };
+    let suffix = if end < chars.len() { "…" } else { "" };
+    let shift = prefix.chars().count();
+
+    let snippet = format!(
+        "{}{}{}",
+        prefix,
+        chars[start..end].iter().collect::<String>(),
+        suffix
+    );
+
+    let highlights = matches
+        .into_iter()
+        .filter(|m| m.start >= start && m.end <= end)
+        .map(|m| Highlight {
+            start: m.start - start + shift,
+            end: m.end - start + shift,
+        })
+        .collect();
+
+    (snippet, highlights)
+}
+
+/// Occurrences of the terms that start at a word boundary, sorted and without overlaps.
+fn find_matches(lower: &[char], terms: &[Vec<char>]) -> Vec<Highlight> {
+    let mut matches = Vec::new();
+
+    for term in terms {
+        if term.len() > lower.len() {
+            continue;
+        }
+
+        for i in 0..=lower.len() - term.len() {
+            let at_boundary = i == 0 || !lower[i - 1].is_alphanumeric();
+            if at_boundary && lower[i..i + term.len()] == term[..] {
+                matches.push(Highlight {
+                    start: i,
+                    end: i + term.len(),
+                });
+            }
+        }
+    }
+
+    matches.sort_by_key(|m| m.start);
+
+    let mut merged: Vec<Highlight> = Vec::new();
+    for m in matches {
+        match merged.last_mut() {
+            Some(last) if m.start < last.end => last.end = last.end.max(m.end),
+            _ => merged.push(m),
+        }
+    }
+
+    merged
+}
+
+// Lowercasing char by char keeps positions aligned with the original text.
+fn lowercase(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(attachment_id: &str, text: &str, score: f32, vector: Vec<f32>) -> VectorSearchResult {
+        VectorSearchResult {
+            text: text.to_string(),
+            attachment_id: attachment_id.to_string(),
+            file_path: format!("{}.txt", attachment_id),
+            section: None,
+            row_start: None,
+            row_end: None,
+            line_start: None,
+            line_end: None,
+            page: None,
+            score,
+            chunk_index: None,
+            start_offset: None,
+            end_offset: None,
+            vector,
+        }
+    }
+
+    fn texts(results: &[VectorSearchResult]) -> Vec<&str> {
+        results.iter().map(|r| r.text.as_str()).collect()
+    }
+
+    fn chunk(index: u32, text: &str, offsets: (u32, u32)) -> VectorSearchResult {
+        VectorSearchResult {
+            chunk_index: Some(index),
+            start_offset: Some(offsets.0),
+            end_offset: Some(offsets.1),
+            ..result("a", text, 0.0, Vec::new())
+        }
+    }
+
+    fn window(attachment_id: &str, index: u32, score: f32, n: u32) -> Window {
+        let hit = VectorSearchResult {
+            chunk_index: Some(index),
+            ..result(attachment_id, "", score, Vec::new())
+        };
+        Window::new(hit, n)
+    }
+
+    fn merge(windows: Vec<Window>) -> Vec<Window> {
+        let mut merged = Vec::new();
+        for window in windows {
+            merged.push(window);
+            merge_windows(&mut merged);
+        }
+        merged
+    }
+
+    fn ranges(windows: &[Window]) -> Vec<Option<(u32, u32)>> {
+        windows.iter().map(|w| w.range).collect()
+    }
+
+    #[test]
+    fn window_of_the_first_chunk_starts_at_zero() {
+        assert_eq!(window("a", 0, 1.0, 2).range, Some((0, 2)));
+        assert_eq!(window("a", 1, 1.0, 2).range, Some((0, 3)));
+        assert_eq!(window("a", 5, 1.0, 2).range, Some((3, 7)));
+    }
+
+    #[test]
+    fn overlapping_windows_are_merged_into_the_first() {
+        let windows = merge(vec![window("a", 4, 0.5, 2), window("a", 7, 0.9, 2)]);
+
+        assert_eq!(ranges(&windows), [Some((2, 9))]);
+        assert_eq!(windows[0].result.score, 0.9);
+    }
+
+    #[test]
+    fn touching_windows_are_merged() {
+        let windows = merge(vec![window("a", 2, 1.0, 1), window("a", 5, 1.0, 1)]);
+
+        assert_eq!(ranges(&windows), [Some((1, 6))]);
+    }
+
+    #[test]
+    fn separate_windows_are_kept() {
+        let windows = merge(vec![
+            window("a", 2, 1.0, 1),
+            window("a", 9, 1.0, 1),
+            window("b", 3, 1.0, 1),
+        ]);
+
+        assert_eq!(
+            ranges(&windows),
+            [Some((1, 3)), Some((8, 10)), Some((2, 4))]
+        );
+    }
+
+    #[test]
+    fn a_window_between_two_others_collapses_them() {
+        let windows = merge(vec![
+            window("a", 1, 0.9, 1),
+            window("b", 4, 0.8, 1),
+            window("a", 7, 0.7, 1),
+            window("a", 4, 0.6, 1),
+        ]);
+
+        assert_eq!(ranges(&windows), [Some((0, 8)), Some((3, 5))]);
+        assert_eq!(windows[0].result.score, 0.9);
+        assert_eq!(windows[1].result.attachment_id, "b");
+    }
+
+    #[test]
+    fn join_chunks_drops_the_overlap() {
+        let chunks = vec![
+            chunk(0, "the quick brown", (0, 15)),
+            chunk(1, "brown fox jumps", (10, 25)),
+            chunk(2, "jumps over", (20, 30)),
+        ];
+
+        assert_eq!(join_chunks(&chunks), "the quick brown fox jumps over");
+    }
+
+    #[test]
+    fn join_chunks_counts_characters_not_bytes() {
+        let chunks = vec![chunk(0, "déjà vu", (0, 7)), chunk(1, "vu ça", (5, 10))];
+
+        assert_eq!(join_chunks(&chunks), "déjà vu ça");
+    }
+
+    #[test]
+    fn join_chunks_puts_separate_chunks_on_their_own_line() {
+        let chunks = vec![
+            chunk(0, "first", (0, 5)),
+            chunk(1, "second", (5, 11)),
+            chunk(2, "inside", (6, 10)),
+        ];
+
+        assert_eq!(join_chunks(&chunks), "first\nsecond");
+    }
+
+    #[test]
+    fn widen_location_covers_every_chunk() {
+        let mut hit = VectorSearchResult {
+            section: Some("Sheet1".to_string()),
+            row_start: Some(10),
+            row_end: Some(12),
+            page: Some(3),
+            ..chunk(1, "", (10, 20))
+        };
+        let chunks = vec![
+            VectorSearchResult {
+                row_start: Some(5),
+                row_end: Some(9),
+                line_start: Some(4),
+                line_end: Some(8),
+                ..hit.clone()
+            },
+            VectorSearchResult {
+                row_start: Some(13),
+                row_end: Some(15),
+                line_start: Some(9),
+                line_end: Some(11),
+                start_offset: Some(18),
+                end_offset: Some(30),
+                ..hit.clone()
+            },
+        ];
+
+        widen_location(&mut hit, &chunks);
+
+        assert_eq!(hit.section.as_deref(), Some("Sheet1"));
+        assert_eq!(hit.page, Some(3));
+        assert_eq!((hit.row_start, hit.row_end), (Some(5), Some(15)));
+        assert_eq!((hit.line_start, hit.line_end), (Some(4), Some(11)));
+        assert_eq!((hit.start_offset, hit.end_offset), (Some(10), Some(30)));
+    }
+
+    #[test]
+    fn widen_location_drops_what_the_chunks_dont_share() {
+        let mut hit = VectorSearchResult {
+            section: Some("Sheet1".to_string()),
+            row_start: Some(1),
+            row_end: Some(2),
+            page: Some(3),
+            ..chunk(0, "", (0, 10))
+        };
+        let chunks = vec![
+            hit.clone(),
+            VectorSearchResult {
+                section: Some("Sheet2".to_string()),
+                page: Some(4),
+                ..hit.clone()
+            },
+        ];
+
+        widen_location(&mut hit, &chunks);
+
+        assert_eq!(hit.section, None);
+        assert_eq!((hit.row_start, hit.row_end), (None, None));
+        assert_eq!(hit.page, None);
+    }
+
+    #[test]
+    fn cosine_similarity_of_vectors() {
+        assert!((cosine_similarity(&[1.0, 0.0], &[2.0, 0.0]) - 1.0).abs() < 1e-6);
+        assert!(cosine_similarity(&[1.0, 0.0], &[0.0, 3.0]).abs() < 1e-6);
+        assert!((cosine_similarity(&[1.0, 0.0], &[-1.0, 0.0]) + 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn cosine_similarity_of_missing_vectors_is_zero() {
+        assert_eq!(cosine_similarity(&[], &[1.0, 0.0]), 0.0);
+        assert_eq!(cosine_similarity(&[1.0, 0.0], &[1.0, 0.0, 0.0]), 0.0);
+        assert_eq!(cosine_similarity(&[0.0, 0.0], &[1.0, 0.0]), 0.0);
+    }
+
+    #[test]
+    fn without_lambda_the_first_candidates_are_picked() {
+        let candidates = vec![
+            result("a", "1", 0.9, vec![1.0, 0.0]),
+            result("a", "2", 0.8, vec![1.0, 0.0]),
+            result("b", "3", 0.7, vec![0.0, 1.0]),
+        ];
+
+        assert_eq!(texts(&select(candidates, 2, None, None)), ["1", "2"]);
+    }
+
+    #[test]
+    fn lambda_of_one_keeps_the_relevance_order() {
+        let candidates = vec![
+            result("a", "1", 0.9, vec![1.0, 0.0]),
+            result("a", "2", 0.8, vec![1.0, 0.0]),
+            result("b", "3", 0.8, vec![0.0, 1.0]),
+            result("b", "4", 0.5, vec![0.0, 1.0]),
+        ];
+
+        let plain = select(candidates.clone(), 4, None, None);
+        let mmr = select(candidates, 4, Some(1.0), None);
+
+        assert_eq!(texts(&mmr), texts(&plain));
+    }
+
+    #[test]
+    fn lambda_of_zero_skips_near_duplicates() {
+        let candidates = vec![
+            result("a", "1", 0.9, vec![1.0, 0.0]),
+            result("a", "1 again", 0.85, vec![0.99, 0.01]),
+            result("b", "2", 0.5, vec![0.0, 1.0]),
+        ];
+
+        let selected = select(candidates, 2, Some(0.0), None);
+
+        assert_eq!(texts(&selected), ["1", "2"]);
+    }
+
+    #[test]
+    fn max_per_file_caps_each_file() {
+        let candidates = vec![
+            result("a", "1", 0.9, Vec::new()),
+            result("a", "2", 0.8, Vec::new()),
+            result("a", "3", 0.7, Vec::new()),
+            result("b", "4", 0.6, Vec::new()),
+        ];
+
+        let selected = select(candidates.clone(), 3, None, Some(1));
+        assert_eq!(texts(&selected), ["1", "4"]);
+
+        let selected = select(candidates, 3, Some(0.5), Some(2));
+        assert_eq!(texts(&selected), ["1", "2", "4"]);
+    }
+
+    #[test]
+    fn candidates_without_vectors_are_picked_by_relevance() {
+        let candidates = vec![
+            result("a", "1", 0.9, Vec::new()),
+            result("a", "2", 0.8, Vec::new()),
+            result("b", "3", 0.1, Vec::new()),
+        ];
+
+        let selected = select(candidates, 2, Some(0.3), None);
+
+        assert_eq!(texts(&selected), ["1", "2"]);
+    }
+}
@@ -2,13 +2,20 @@ use std::sync::Arc;
 use tauri::async_runtime::Mutex;
 
 use crate::{
-    ai::{embeds::EmbedModel, llama::Model},
-    db::db_manager::DBManager,
+    ai::{embeds::EmbedModel, llama::Model, rerank::Reranker},
+    db::{db_manager::DBManager, settings::Settings},
+    ingestion::IngestionQueue,
+    store::FileStore,
 };
 
 #[derive(Clone)]
 pub struct AppState {
     pub db: Arc<DBManager>,
     pub embeddings_model: Arc<Mutex<EmbedModel>>,
+    /// Scoring is blocking work, so it's locked from a blocking thread.
+    pub reranker: Arc<std::sync::Mutex<Reranker>>,
     pub chat_model: Arc<Mutex<Model>>,
+    pub settings: Arc<Mutex<Settings>>,
+    pub ingestion: IngestionQueue,
+    pub files: FileStore,
 }
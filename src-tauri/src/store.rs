@@ -0,0 +1,125 @@
+use std::{
+    fs::File,
+    io::{Read, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::db::attachments::AttachmentRepository;
+
+/// Copies of the imported files, named after the SHA-256 of their content so
+/// attachments keep working when the original is moved or deleted. A file with
+/// the same content is only stored once.
+#[derive(Clone)]
+pub struct FileStore {
+    root: PathBuf,
+}
+
+pub struct StoredFile {
+    pub path: PathBuf,
+    /// SHA-256 of the content, as hex.
+    pub hash: String,
+}
+
+impl FileStore {
+    pub fn new(root: PathBuf) -> Result<Self> {
+        std::fs::create_dir_all(&root).context("Could not create the file store directory.")?;
+        Ok(Self { root })
+    }
+
+    /// Copies `source` into the store, hashing it on the way.
+    pub fn put(&self, source: &Path) -> Result<StoredFile> {
+        let mut input = File::open(source).context("Failed to open the file.")?;
+
+        // Written under a temporary name first, the hash isn't known before the end.
+        let temp_path = self.root.join(format!(".{}.tmp", Uuid::new_v4()));
+        let mut output =
+            File::create(&temp_path).context("Failed to create a file in the file store.")?;
+
+        let copied = copy_hashing(&mut input, &mut output).and_then(|hash| {
+            output
+                .sync_all()
+                .context("Failed to copy the file into the file store.")?;
+            Ok(hash)
+        });
+
+        let hash = match copied {
+            Ok(hash) => hash,
+            Err(e) => {
+                std::fs::remove_file(&temp_path).ok();
+                return Err(e);
+            }
+        };
+
+        // The extension is kept, extractors pick the format from it.
+        let extension = source
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(str::to_lowercase);
+        let file_name = match extension {
+            Some(extension) => format!("{}.{}", hash, extension),
+            None => hash.clone(),
+        };
+        let path = self.root.join(&hash[..2]).join(file_name);
+
+        if path.exists() {
+            std::fs::remove_file(&temp_path).ok();
+        } else {
+            std::fs::create_dir_all(path.parent().unwrap_or(&self.root))
+                .context("Could not create the file store directory.")?;
+            std::fs::rename(&temp_path, &path)
+                .context("Failed to move the file into the file store.")?;
+        }
+
+        Ok(StoredFile { path, hash })
+    }
+
+    /// SHA-256 of a file, as hex, to tell whether it changed since it was stored.
+    pub fn hash(path: &Path) -> Result<String> {
+        let mut input = File::open(path).context("Failed to open the file.")?;
+        copy_hashing(&mut input, &mut std::io::sink())
+    }
+
+    /// Deletes a stored file once no attachment points at it anymore. Paths outside
+    /// the store, from attachments imported before it existed, are never touched.
+    pub async fn release(&self, attachments: &AttachmentRepository, path: &str) -> Result<()> {
+        if !Path::new(path).starts_with(&self.root) {
+            return Ok(());
+        }
+
+        if attachments.count_by_file_path(path).await? > 0 {
+            return Ok(());
+        }
+
+        match std::fs::remove_file(path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                Err(e).context("Failed to delete the stored file.")
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Copies `input` to `output` and returns the SHA-256 of what was copied, as hex.
+fn copy_hashing(input: &mut impl Read, output: &mut impl Write) -> Result<String> {
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; 64 * 1024];
+
+    loop {
+        let read = input
+            .read(&mut buffer)
+            .context("Failed to read the file.")?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+        output
+            .write_all(&buffer[..read])
+            .context("Failed to copy the file into the file store.")?;
+    }
+
+    Ok(format!("{:x}", hasher.finalize()))
+}
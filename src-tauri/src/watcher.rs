@@ -0,0 +1,252 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+use tauri::{async_runtime::Mutex, AppHandle, Emitter, Manager};
+
+use crate::{
+    db::{attachments::Attachment, settings::SourceWatch, watched_folders::WatchedFolder},
+    import::{self, ImportSummary},
+    state::AppState,
+    store::FileStore,
+};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+// The poller and the commands can both sync a folder, they must not overlap.
+static SYNC_LOCK: Mutex<()> = Mutex::const_new(());
+
+/// Outcome of syncing a watched folder.
+#[derive(Serialize, Debug, Default)]
+pub struct FolderSync {
+    /// Files that showed up in the folder.
+    pub imported: ImportSummary,
+    /// Files that changed, by name.
+    pub reindexed: Vec<String>,
+    /// Files that were deleted from the folder, by name.
+    pub removed: Vec<String>,
+}
+
+/// Every now and then, syncs the watched folders and checks the original files of
+/// the other attachments. The ones that changed since they were imported are
+/// flagged or re-indexed, following the `source_watch` setting.
+pub fn spawn(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            if let Err(e) = sync_folders(&app).await {
+                eprintln!("Could not sync the watched folders: {}", e);
+            }
+
+            if let Err(e) = check_sources(&app).await {
+                eprintln!("Could not check the source files: {}", e);
+            }
+
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    });
+}
+
+async fn sync_folders(app: &AppHandle) -> Result<()> {
+    let state = app.state::<AppState>();
+
+    for folder in state.db.get_watched_folders_repository().get_all().await? {
+        if let Err(e) = sync_folder(app, &state, &folder, false).await {
+            eprintln!("Could not sync {}: {}", folder.path, e);
+        }
+    }
+
+    Ok(())
+}
+
+/// Brings a notebook in line with one of its watched folders: new files are
+/// imported, changed ones re-indexed and deleted ones removed. The outcome is
+/// stored on the folder. Files that weren't imported last time are only tried
+/// again once they change, or with `retry_skipped`.
+pub async fn sync_folder(
+    app: &AppHandle,
+    state: &AppState,
+    folder: &WatchedFolder,
+    retry_skipped: bool,
+) -> Result<FolderSync> {
+    let _guard = SYNC_LOCK.lock().await;
+
+    let result = sync_files(app, state, folder, retry_skipped).await;
+
+    state
+        .db
+        .get_watched_folders_repository()
+        .update_sync(&folder.id, result.as_ref().err().map(|e| e.to_string()))
+        .await?;
+
+    result
+}
+
+async fn sync_files(
+    app: &AppHandle,
+    state: &AppState,
+    folder: &WatchedFolder,
+    retry_skipped: bool,
+) -> Result<FolderSync> {
+    let root = PathBuf::from(&folder.path);
+
+    // A folder that can't be reached, like an unplugged drive, isn't an empty
+    // folder. Its files are kept.
+    if !root.is_dir() {
+        bail!("The folder {} can't be found.", folder.path);
+    }
+
+    let files = import::collect_folder(&root)?;
+    let present: HashSet<String> = files
+        .iter()
+        .map(|p| p.to_string_lossy().to_string())
+        .collect();
+
+    let synced = state
+        .db
+        .get_attachments_repository()
+        .get_by_watched_folder(&folder.id)
+        .await?;
+    let known: HashSet<String> = synced.iter().map(|a| a.source_path().to_string()).collect();
+
+    let mut summary = FolderSync::default();
+
+    for attachment in synced {
+        let name = attachment.file_name.clone();
+
+        if !present.contains(attachment.source_path()) {
+            match import::remove_attachment(state, attachment).await {
+                Ok(()) => summary.removed.push(name),
+                Err(e) => eprintln!("Could not remove {}: {}", name, e),
+            }
+        } else if matches!(attachment.status.as_str(), "ready" | "error") {
+            match check_source(app, state, SourceWatch::Reindex, attachment).await {
+                Ok(true) => summary.reindexed.push(name),
+                Ok(false) => {}
+                Err(e) => eprintln!("Could not check {}: {}", name, e),
+            }
+        }
+    }
+
+    // Duplicates and files that can't be read would otherwise be copied into the
+    // store and dropped again on every poll.
+    let mut skipped = HashMap::new();
+    let mut new_files = Vec::new();
+    for path in files {
+        let key = path.to_string_lossy().to_string();
+        if known.contains(&key) {
+            continue;
+        }
+
+        let modified = import::modified_at(&path);
+        if !retry_skipped && folder.skipped.get(&key) == Some(&modified) {
+            skipped.insert(key, modified);
+        } else {
+            new_files.push(path);
+        }
+    }
+
+    summary.imported =
+        import::import_files(app, state, &folder.notebook_id, new_files, Some(&folder.id)).await?;
+
+    for issue in summary
+        .imported
+        .skipped
+        .iter()
+        .chain(&summary.imported.rejected)
+    {
+        let modified = import::modified_at(Path::new(&issue.file_path));
+        skipped.insert(issue.file_path.clone(), modified);
+    }
+
+    state
+        .db
+        .get_watched_folders_repository()
+        .update_skipped(&folder.id, &skipped)
+        .await?;
+
+    Ok(summary)
+}
+
+async fn check_sources(app: &AppHandle) -> Result<()> {
+    let state = app.state::<AppState>();
+    let mode = state.settings.lock().await.source_watch;
+    if mode == SourceWatch::Off {
+        return Ok(());
+    }
+
+    for attachment in state.db.get_attachments_repository().get_watched().await? {
+        // Already flagged, nothing more to tell.
+        if attachment.stale && mode == SourceWatch::Flag {
+            continue;
+        }
+
+        let name = attachment.file_name.clone();
+        if let Err(e) = check_source(app, &state, mode, attachment).await {
+            eprintln!("Could not check {}: {}", name, e);
+        }
+    }
+
+    Ok(())
+}
+
+async fn check_source(
+    app: &AppHandle,
+    state: &AppState,
+    mode: SourceWatch,
+    attachment: Attachment,
+) -> Result<bool> {
+    let Some(original) = attachment.original_path.clone() else {
+        return Ok(false);
+    };
+
+    // A missing original isn't a change, the stored copy is still there.
+    let Some(modified) = import::modified_at(Path::new(&original)) else {
+        return Ok(false);
+    };
+
+    // Once flagged, the content is already known to differ.
+    if !attachment.stale {
+        if attachment.source_modified_at.is_some_and(|m| modified <= m) {
+            return Ok(false);
+        }
+
+        // Saving a file without changing it also moves its modification time,
+        // the hash tells whether the content changed.
+        let hash =
+            tauri::async_runtime::spawn_blocking(move || FileStore::hash(Path::new(&original)))
+                .await
+                .context("Failed to hash the original file.")??;
+
+        if attachment.content_hash.as_deref() == Some(hash.as_str()) {
+            state
+                .db
+                .get_attachments_repository()
+                .update_source_modified_at(&attachment.id, modified)
+                .await?;
+            return Ok(false);
+        }
+    }
+
+    match mode {
+        SourceWatch::Reindex => {
+            if state.ingestion.is_active(&attachment.id) {
+                return Ok(false);
+            }
+            import::reindex(app, state, attachment).await?;
+        }
+        _ => {
+            state
+                .db
+                .get_attachments_repository()
+                .mark_stale(&attachment.id)
+                .await?;
+            app.emit("attachment-stale", &attachment.id).ok();
+        }
+    }
+
+    Ok(true)
+}
@@ -2,23 +2,47 @@ import {
   Component,
   createSignal,
   createResource,
+  createEffect,
+  on,
   For,
   Show,
   onMount,
   onCleanup,
 } from "solid-js";
-import { ArrowLeft, FileText, CloudUpload, Trash2 } from "lucide-solid";
+import {
+  ArrowLeft,
+  FileText,
+  CloudUpload,
+  FolderInput,
+  FolderSync as FolderSyncIcon,
+  RefreshCw,
+  Trash2,
+  X,
+} from "lucide-solid";
 import { A, useParams } from "@solidjs/router";
 import {
   getAttachments,
-  uploadFile,
+  uploadFiles,
+  importFolder,
   deleteAttachment,
+  reindexAttachment,
+  watchFolder,
+  getWatchedFolders,
+  syncWatchedFolder,
+  unwatchFolder,
+  FolderSync,
+  ImportSummary,
+  AppError,
   ProcessingStatus,
 } from "../../lib/commands";
 import { showToast } from "../../lib/toast";
+import { type Result } from "../../lib/utils";
 import { listen } from "@tauri-apps/api/event";
 
-export const ResizableSidebar: Component = () => {
+export const ResizableSidebar: Component<{
+  selected: string[];
+  onSelect: (ids: string[]) => void;
+}> = (props) => {
   const params = useParams<{ id: string }>();
   const [width, setWidth] = createSignal(280);
   let isResizing = false;
@@ -46,7 +70,11 @@ export const ResizableSidebar: Component = () => {
   return (
     <div class="flex h-full overflow-hidden">
       <div style={{ width: `${width()}px` }} class="shrink-0">
-        <FileSidebar notebookId={params.id} />
+        <FileSidebar
+          notebookId={params.id}
+          selected={props.selected}
+          onSelect={props.onSelect}
+        />
       </div>
       <div
         onMouseDown={startResizing}
@@ -56,7 +84,11 @@ export const ResizableSidebar: Component = () => {
   );
 };
 
-const FileSidebar: Component<{ notebookId: string }> = (props) => {
+const FileSidebar: Component<{
+  notebookId: string;
+  selected: string[];
+  onSelect: (ids: string[]) => void;
+}> = (props) => {
   const [processingMap, setProcessingMap] = createSignal<
     Record<string, ProcessingStatus>
   >({});
@@ -72,6 +104,25 @@ const FileSidebar: Component<{ notebookId: string }> = (props) => {
     },
   );
 
+  // Files that were deleted, also by a folder sync, can't stay selected.
+  createEffect(
+    on(attachments, (files) => {
+      if (!files) return;
+      const ids = new Set(files.map((file) => file.id));
+      const kept = props.selected.filter((id) => ids.has(id));
+      if (kept.length !== props.selected.length) props.onSelect(kept);
+    }),
+  );
+
+  const [watchedFolders, { refetch: refetchFolders }] = createResource(
+    () => props.notebookId,
+    async (id) => {
+      const [err, data] = await getWatchedFolders(id);
+      if (err) throw err;
+      return data;
+    },
+  );
+
   onMount(async () => {
     const unlistenStart = await listen<string>("processing-start", (event) => {
       setProcessingMap((prev) => ({ ...prev, [event.payload]: "pending" }));
@@ -93,28 +144,56 @@ const FileSidebar: Component<{ notebookId: string }> = (props) => {
       },
     );
 
+    const unlistenStale = await listen<string>("attachment-stale", () =>
+      refetch(),
+    );
+
     onCleanup(() => {
       unlistenStart();
       unlistenSuccess();
       unlistenError();
+      unlistenStale();
     });
   });
 
-  const handleUpload = async () => {
-    const [err, file] = await uploadFile(props.notebookId);
+  const handleImport = async (result: Result<ImportSummary, AppError>) => {
+    const [err, summary] = result;
 
     if (err) {
-      if (err.reason !== "No file selected") {
+      if (
+        err.reason !== "No file selected" &&
+        err.reason !== "No folder selected"
+      ) {
         showToast({ message: err.reason, type: "error" });
       }
       return;
     }
 
-    setProcessingMap((prev) => ({ ...prev, [file.id]: "pending" }));
-    showToast({ message: `Uploaded ${file!.file_name}`, type: "success" });
+    for (const file of summary.accepted) {
+      setProcessingMap((prev) => ({ ...prev, [file.id]: "pending" }));
+    }
+
+    const parts = [`Imported ${summary.accepted.length} file(s)`];
+    if (summary.skipped.length > 0) {
+      parts.push(`${summary.skipped.length} skipped as duplicates`);
+    }
+    if (summary.rejected.length > 0) {
+      parts.push(`${summary.rejected.length} rejected`);
+    }
+
+    showToast({
+      message: parts.join(", "),
+      type: summary.rejected.length > 0 ? "error" : "success",
+    });
     refetch();
   };
 
+  const handleUpload = async () =>
+    handleImport(await uploadFiles(props.notebookId));
+
+  const handleImportFolder = async () =>
+    handleImport(await importFolder(props.notebookId));
+
   const handleDelete = async (id: string, name: string) => {
     if (!confirm(`Delete ${name}?`)) return;
     const [err] = await deleteAttachment(id);
@@ -125,6 +204,60 @@ const FileSidebar: Component<{ notebookId: string }> = (props) => {
     }
   };
 
+  const handleFolderSync = async (result: Result<FolderSync, AppError>) => {
+    const [err, sync] = result;
+    refetchFolders();
+
+    if (err) {
+      if (err.reason !== "No folder selected") {
+        showToast({ message: err.reason, type: "error" });
+      }
+      return;
+    }
+
+    for (const file of sync.imported.accepted) {
+      setProcessingMap((prev) => ({ ...prev, [file.id]: "pending" }));
+    }
+
+    showToast({
+      message: `Synced: ${sync.imported.accepted.length} new, ${sync.reindexed.length} changed, ${sync.removed.length} removed`,
+      type: sync.imported.rejected.length > 0 ? "error" : "success",
+    });
+    refetch();
+  };
+
+  const handleWatchFolder = async () =>
+    handleFolderSync(await watchFolder(props.notebookId));
+
+  const handleUnwatch = async (id: string, path: string) => {
+    if (!confirm(`Stop syncing ${path}? Its files stay in the notebook.`))
+      return;
+    const [err] = await unwatchFolder(id);
+    if (err) {
+      showToast({ message: err.reason, type: "error" });
+    } else {
+      refetchFolders();
+    }
+  };
+
+  // Chat only uses the ticked files, or all of them when none is ticked.
+  const toggleSelected = (id: string) =>
+    props.onSelect(
+      props.selected.includes(id)
+        ? props.selected.filter((s) => s !== id)
+        : [...props.selected, id],
+    );
+
+  const handleReindex = async (id: string) => {
+    const [err] = await reindexAttachment(id);
+    if (err) {
+      showToast({ message: err.reason, type: "error" });
+    } else {
+      setProcessingMap((prev) => ({ ...prev, [id]: "pending" }));
+      refetch();
+    }
+  };
+
   return (
     <aside class="w-full h-full border-r border-zinc-800 flex flex-col bg-zinc-900/10">
       <div class="p-4 border-b border-zinc-800 flex items-center justify-between">
@@ -137,9 +270,54 @@ const FileSidebar: Component<{ notebookId: string }> = (props) => {
         <span class="text-sm font-medium uppercase tracking-widest text-zinc-500">
           Files
         </span>
-        <div></div>
+        <Show when={props.selected.length > 0} fallback={<div></div>}>
+          <button
+            onClick={() => props.onSelect([])}
+            title="Use all files"
+            class="text-[9px] uppercase tracking-widest text-zinc-500 hover:text-white"
+          >
+            {props.selected.length} selected
+          </button>
+        </Show>
       </div>
 
+      <Show when={(watchedFolders()?.length ?? 0) > 0}>
+        <div class="p-2 border-b border-zinc-800 space-y-1">
+          <For each={watchedFolders()}>
+            {(folder) => (
+              <div class="flex items-center gap-2 px-2 py-1 group">
+                <FolderSyncIcon
+                  size={12}
+                  class={folder.last_error ? "text-red-500" : "text-zinc-600"}
+                />
+                <span
+                  class="text-[10px] text-zinc-500 truncate flex-1"
+                  title={folder.last_error ?? folder.path}
+                >
+                  {folder.path}
+                </span>
+                <button
+                  onClick={async () =>
+                    handleFolderSync(await syncWatchedFolder(folder.id))
+                  }
+                  title="Sync now"
+                  class="p-1 hover:text-zinc-200 text-zinc-500 transition-all"
+                >
+                  <RefreshCw size={10} />
+                </button>
+                <button
+                  onClick={() => handleUnwatch(folder.id, folder.path)}
+                  title="Stop syncing"
+                  class="p-1 hover:text-red-400 text-zinc-500 transition-all"
+                >
+                  <X size={10} />
+                </button>
+              </div>
+            )}
+          </For>
+        </div>
+      </Show>
+
       <div class="flex-1 overflow-y-auto p-2 space-y-1">
         <Show
           when={!attachments.loading}
@@ -149,7 +327,14 @@ const FileSidebar: Component<{ notebookId: string }> = (props) => {
             {(file) => (
               <FileItem
                 name={file.file_name}
+                tooltip={[file.metadata.title, file.metadata.url, file.original_path]
+                  .filter(Boolean)
+                  .join("\n")}
                 status={processingMap()[file.id] ?? file.status}
+                stale={file.stale}
+                selected={props.selected.includes(file.id)}
+                onToggle={() => toggleSelected(file.id)}
+                onReindex={() => handleReindex(file.id)}
                 onDelete={() => handleDelete(file.id, file.file_name)}
               />
             )}
@@ -168,7 +353,21 @@ const FileSidebar: Component<{ notebookId: string }> = (props) => {
           class="btn btn-outline border-zinc-700 hover:border-zinc-400 hover:bg-transparent text-zinc-400 hover:text-white w-full btn-sm rounded-sm text-[10px] tracking-widest uppercase"
         >
           <CloudUpload class="size-4" />
-          Upload File
+          Upload Files
+        </button>
+        <button
+          onClick={handleImportFolder}
+          class="btn btn-outline border-zinc-700 hover:border-zinc-400 hover:bg-transparent text-zinc-400 hover:text-white w-full btn-sm rounded-sm text-[10px] tracking-widest uppercase mt-2"
+        >
+          <FolderInput class="size-4" />
+          Import Folder
+        </button>
+        <button
+          onClick={handleWatchFolder}
+          class="btn btn-outline border-zinc-700 hover:border-zinc-400 hover:bg-transparent text-zinc-400 hover:text-white w-full btn-sm rounded-sm text-[10px] tracking-widest uppercase mt-2"
+        >
+          <FolderSyncIcon class="size-4" />
+          Watch Folder
         </button>
       </div>
     </aside>
@@ -177,12 +376,28 @@ const FileSidebar: Component<{ notebookId: string }> = (props) => {
 
 const FileItem: Component<{
   name: string;
+  tooltip?: string;
   status: ProcessingStatus;
+  stale: boolean;
+  selected: boolean;
+  onToggle: () => void;
+  onReindex: () => void;
   onDelete: () => void;
 }> = (props) => (
   <div class="flex items-center gap-3 p-2 rounded-sm hover:bg-zinc-800/50 cursor-pointer group transition-colors">
+    <input
+      type="checkbox"
+      checked={props.selected}
+      onChange={props.onToggle}
+      onClick={(e) => e.stopPropagation()}
+      title="Only ask about the selected files"
+      class="checkbox checkbox-xs rounded-sm border-zinc-700"
+    />
     <FileText size={14} class="text-zinc-600 group-hover:text-zinc-300" />
-    <span class="text-xs text-zinc-400 group-hover:text-zinc-200 truncate flex-1">
+    <span
+      class="text-xs text-zinc-400 group-hover:text-zinc-200 truncate flex-1"
+      title={props.tooltip || props.name}
+    >
       {props.name}
     </span>
 
@@ -196,6 +411,24 @@ const FileItem: Component<{
         Failed
       </span>
     </Show>
+    <Show when={props.stale && props.status !== "pending"}>
+      <span class="text-[9px] uppercase tracking-widest text-orange-500">
+        Outdated
+      </span>
+    </Show>
+
+    <Show when={props.status !== "pending"}>
+      <button
+        onClick={(e) => {
+          e.stopPropagation();
+          props.onReindex();
+        }}
+        title="Re-index"
+        class="p-1 hover:text-zinc-200 text-zinc-500 transition-all"
+      >
+        <RefreshCw size={12} />
+      </button>
+    </Show>
 
     <button
       onClick={(e) => {
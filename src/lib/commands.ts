@@ -10,24 +10,51 @@ export interface Notebook {
   last_accessed: number;
 }
 
+export interface Citation {
+  marker: number;
+  attachment_id: string;
+  file_name: string;
+  text: string;
+  score: number;
+  page: number | null;
+  section: string | null;
+  row_start: number | null;
+  row_end: number | null;
+  line_start: number | null;
+  line_end: number | null;
+}
+
 export interface ChatEntry {
   id: string;
   notebook_id: string;
   role: MessageRole;
   message: string;
   timestamp: number;
+  citations: Citation[];
 }
 
-export type ProcessingStatus = "pending" | "ready" | "error";
+export type ProcessingStatus = "pending" | "ready" | "error" | "cancelled";
 export interface Attachment {
   id: string;
   notebookId: string;
   file_name: string;
   file_path: string;
+  original_path: string | null;
+  content_hash: string | null;
+  source_modified_at: number | null;
+  /** The original file changed since it was indexed. */
+  stale: boolean;
+  watched_folder_id: string | null;
   file_size: number;
   file_type: string;
   status: ProcessingStatus;
   created_at: number;
+  metadata: AttachmentMetadata;
+}
+
+export interface AttachmentMetadata {
+  title: string | null;
+  url: string | null;
 }
 
 export interface AppError {
@@ -83,10 +110,95 @@ export async function getAttachments(
   return call<Attachment[]>("get_attachments", { notebookId });
 }
 
-export async function uploadFile(
+export interface ImportIssue {
+  file_path: string;
+  reason: string;
+}
+
+export interface ImportSummary {
+  accepted: Attachment[];
+  skipped: ImportIssue[];
+  rejected: ImportIssue[];
+}
+
+export async function uploadFiles(
+  notebookId: string,
+): Promise<Result<ImportSummary, AppError>> {
+  return call<ImportSummary>("upload_files", { notebookId });
+}
+
+export async function importFolder(
+  notebookId: string,
+): Promise<Result<ImportSummary, AppError>> {
+  return call<ImportSummary>("import_folder", { notebookId });
+}
+
+export interface WatchedFolder {
+  id: string;
+  notebook_id: string;
+  path: string;
+  created_at: number;
+  last_synced_at: number | null;
+  last_error: string | null;
+}
+
+export interface FolderSync {
+  imported: ImportSummary;
+  reindexed: string[];
+  removed: string[];
+}
+
+export async function watchFolder(
   notebookId: string,
+): Promise<Result<FolderSync, AppError>> {
+  return call<FolderSync>("watch_folder", { notebookId });
+}
+
+export async function getWatchedFolders(
+  notebookId: string,
+): Promise<Result<WatchedFolder[], AppError>> {
+  return call<WatchedFolder[]>("get_watched_folders", { notebookId });
+}
+
+export async function syncWatchedFolder(
+  id: string,
+): Promise<Result<FolderSync, AppError>> {
+  return call<FolderSync>("sync_watched_folder", { id });
+}
+
+export async function unwatchFolder(
+  id: string,
+): Promise<Result<null, AppError>> {
+  return call<null>("unwatch_folder", { id });
+}
+
+export type IngestionStage =
+  | "queued"
+  | "extracted"
+  | "chunked"
+  | "embedding"
+  | "storing"
+  | "retrying";
+
+/** Payload of the "ingestion-progress" event. */
+export interface IngestionProgress {
+  jobId: string;
+  attachmentId: string;
+  stage: IngestionStage;
+  done: number;
+  total: number;
+}
+
+export async function cancelIngestion(
+  attachmentId: string,
+): Promise<Result<null, AppError>> {
+  return call<null>("cancel_ingestion", { attachmentId });
+}
+
+export async function reindexAttachment(
+  id: string,
 ): Promise<Result<Attachment, AppError>> {
-  return call<Attachment>("upload_file", { notebookId });
+  return call<Attachment>("reindex_attachment", { id });
 }
 
 export async function deleteAttachment(
@@ -95,9 +207,129 @@ export async function deleteAttachment(
   return call<null>("delete_attachment", { id });
 }
 
+export type SearchMode = "vector" | "hybrid";
+
 export async function chat(
   notebookId: string,
   message: string,
-): Promise<Result<string, AppError>> {
-  return call<string>("chat", { notebookId, message });
+  searchMode?: SearchMode,
+  attachmentIds?: string[],
+): Promise<Result<ChatEntry, AppError>> {
+  return call<ChatEntry>("chat", {
+    notebookId,
+    message,
+    searchMode,
+    attachmentIds,
+  });
+}
+
+export interface ChatTokenEvent {
+  requestId: string;
+  token: string;
+}
+
+export interface ChatDoneEvent {
+  requestId: string;
+  entry: ChatEntry;
+}
+
+export interface ChatErrorEvent {
+  requestId: string;
+  reason: string;
+}
+
+// Answer tokens arrive through the "chat-token", "chat-done" and "chat-error" events.
+export async function chatStream(
+  notebookId: string,
+  message: string,
+  requestId: string,
+  searchMode?: SearchMode,
+  attachmentIds?: string[],
+): Promise<Result<null, AppError>> {
+  return call<null>("chat_stream", {
+    notebookId,
+    message,
+    requestId,
+    searchMode,
+    attachmentIds,
+  });
+}
+
+export type ProviderConfig =
+  | { kind: "ollama"; host: string; model: string }
+  | { kind: "open_ai"; base_url: string; model: string; api_key: string | null };
+
+export type EmbeddingModelKind =
+  | "all_mini_lm_l6_v2"
+  | "paraphrase_multilingual_mini_lm_l12_v2"
+  | "multilingual_e5_small"
+  | "bge_small_en_v15";
+
+export type RerankerModelKind =
+  | "bge_reranker_base"
+  | "bge_reranker_v2_m3"
+  | "jina_reranker_v1_turbo_en"
+  | "jina_reranker_v2_base_multilingual";
+
+export type SourceWatch = "off" | "flag" | "reindex";
+
+export interface Settings {
+  provider: ProviderConfig;
+  top_k: number;
+  search_mode: SearchMode;
+  embedding_model: EmbeddingModelKind;
+  batch_size: number;
+  chunk_size: number;
+  chunk_overlap: number;
+  source_watch: SourceWatch;
+  rerank: boolean;
+  reranker_model: RerankerModelKind;
+  candidate_pool: number;
+  mmr: boolean;
+  mmr_lambda: number;
+  max_chunks_per_file: number | null;
+  context_chunks: number;
+}
+
+export async function getSettings(): Promise<Result<Settings, AppError>> {
+  return call<Settings>("get_settings");
+}
+
+export async function updateSettings(
+  settings: Settings,
+): Promise<Result<Settings, AppError>> {
+  return call<Settings>("update_settings", { settings });
+}
+
+export interface Highlight {
+  start: number;
+  end: number;
+}
+
+export interface SearchHit {
+  text: string;
+  attachment_id: string;
+  file_path: string;
+  section: string | null;
+  row_start: number | null;
+  row_end: number | null;
+  line_start: number | null;
+  line_end: number | null;
+  page: number | null;
+  score: number;
+  file_name: string;
+  snippet: string;
+  highlights: Highlight[];
+}
+
+export async function searchNotebook(
+  notebookId: string,
+  query: string,
+  options: {
+    topK?: number;
+    attachmentIds?: string[];
+    searchMode?: SearchMode;
+  } = {},
+): Promise<Result<SearchHit[], AppError>> {
+  return call<SearchHit[]>("search_notebook", { notebookId, query, ...options });
 }
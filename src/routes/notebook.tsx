@@ -9,6 +9,7 @@ import { showToast } from "../lib/toast";
 export function NotebookRoute() {
   const params = useParams<{ id: string }>();
   const [isThinking, setIsThinking] = createSignal(false);
+  const [selected, setSelected] = createSignal<string[]>([]);
   const [optimisticMessages, setOptimisticMessages] = createSignal<
     { role: "user" | "assistant"; message: string }[]
   >([]);
@@ -32,7 +33,12 @@ export function NotebookRoute() {
     setOptimisticMessages((prev) => [...prev, { role: "user", message: msg }]);
     setIsThinking(true);
 
-    const [err, response] = await chat(params.id, msg);
+    const [err, response] = await chat(
+      params.id,
+      msg,
+      undefined,
+      selected().length > 0 ? selected() : undefined,
+    );
 
     setIsThinking(false);
     setOptimisticMessages([]);
@@ -47,7 +53,7 @@ export function NotebookRoute() {
 
   return (
     <div class="flex h-screen bg-black text-zinc-100 overflow-hidden">
-      <ResizableSidebar />
+      <ResizableSidebar selected={selected()} onSelect={setSelected} />
       <main class="flex-1 flex flex-col relative">
         <header class="h-14 border-b border-zinc-800 flex items-center px-6 justify-between bg-black/50 backdrop-blur-md">
           <h1 class="text-sm font-medium text-zinc-200">